@@ -0,0 +1,174 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// What a span of seconds stands for, which decides how it is printed.
+/// Dates and times are counted from 1970-01-01 00:00 in local time, so that
+/// adding days never runs into daylight saving changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Calendar {
+    /// A day, `2026-10-18`.
+    Date,
+    /// A time of day, `14:30`, taken to be today.
+    Time,
+    /// A day and a time, `2026-10-18 14:30`.
+    DateTime,
+    /// A span of time, `3h 20m`.
+    Duration,
+}
+
+impl Calendar {
+    /// Whether values of this kind are points in time rather than spans.
+    pub fn is_point(self) -> bool {
+        self != Calendar::Duration
+    }
+}
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Seconds in each unit a duration literal such as `3h` can be written in.
+pub fn duration_unit(unit: char) -> Option<&'static str> {
+    Some(match unit {
+        'w' => "604800",
+        'd' => "86400",
+        'h' => "3600",
+        'm' => "60",
+        's' => "1",
+        _ => return None,
+    })
+}
+
+/// Seconds since the epoch of a date or time literal, `None` when it names
+/// a day or time that does not exist, such as `2026-02-30`.
+pub fn timestamp(kind: Calendar, text: &str) -> Option<i64> {
+    let moment = match kind {
+        Calendar::Date => NaiveDate::parse_from_str(text, DATE_FORMAT)
+            .ok()?
+            .and_time(NaiveTime::MIN),
+        Calendar::Time => today().and_time(parse_time(text)?),
+        Calendar::DateTime => {
+            let (date, time) = text.split_once(['T', ' '])?;
+            NaiveDate::parse_from_str(date, DATE_FORMAT)
+                .ok()?
+                .and_time(parse_time(time)?)
+        }
+        Calendar::Duration => return None,
+    };
+    Some(moment.and_utc().timestamp())
+}
+
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .ok()
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Seconds since the epoch of the start of today.
+pub fn midnight() -> i64 {
+    today().and_time(NaiveTime::MIN).and_utc().timestamp()
+}
+
+/// Seconds since the epoch of the current second.
+pub fn now() -> i64 {
+    Local::now().naive_local().and_utc().timestamp()
+}
+
+/// Writes `seconds` as the kind of value it stands for: `2026-12-02`,
+/// `15:15` or `4h 5m`. `None` for points outside the calendar.
+pub fn format(kind: Calendar, seconds: f64) -> Option<String> {
+    if kind == Calendar::Duration {
+        return Some(format_duration(seconds));
+    }
+    let moment = to_datetime(seconds)?;
+    let time = if moment.second() == 0 {
+        moment.format("%H:%M")
+    } else {
+        moment.format("%H:%M:%S")
+    };
+    Some(match kind {
+        Calendar::Date if moment.time() == NaiveTime::MIN => moment.format(DATE_FORMAT).to_string(),
+        // Times of day other than today's are given with their date.
+        Calendar::Time if moment.date() == today() => time.to_string(),
+        _ => format!("{} {}", moment.format(DATE_FORMAT), time),
+    })
+}
+
+/// Whether `seconds` since the epoch falls on a day the calendar can show.
+pub fn in_range(seconds: f64) -> bool {
+    to_datetime(seconds).is_some()
+}
+
+fn to_datetime(seconds: f64) -> Option<NaiveDateTime> {
+    if !seconds.is_finite() || seconds.abs() > i64::MAX as f64 {
+        return None;
+    }
+    DateTime::from_timestamp(seconds.round() as i64, 0).map(|moment| moment.naive_utc())
+}
+
+/// Writes a duration in days, hours, minutes and seconds, leaving out the
+/// parts that are zero: `68d`, `4h 5m`, `1m 30.5s`.
+fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return format!("{}s", seconds);
+    }
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let mut rest = seconds.abs();
+    let mut parts = vec![];
+    for (unit, size) in [("d", 86400.0), ("h", 3600.0), ("m", 60.0)] {
+        let count = (rest / size).floor();
+        if count > 0.0 {
+            parts.push(format!("{}{}", count, unit));
+            rest -= count * size;
+        }
+    }
+    // Rounded to milliseconds, which also hides float noise.
+    let rest = (rest * 1000.0).round() / 1000.0;
+    if rest > 0.0 || parts.is_empty() {
+        parts.push(format!("{}s", rest));
+    }
+    format!("{}{}", sign, parts.join(" "))
+}
+
+/// Length of a date such as `2026-10-18`, possibly followed by a time as
+/// in `2026-10-18T14:30`, at the start of `text`.
+pub fn date_len(text: &[char]) -> Option<usize> {
+    let digits = |from: usize, to: usize| {
+        text.get(from..to)
+            .is_some_and(|part| part.iter().all(char::is_ascii_digit))
+    };
+    let is_date = digits(0, 4)
+        && text.get(4) == Some(&'-')
+        && digits(5, 7)
+        && text.get(7) == Some(&'-')
+        && digits(8, 10);
+    if !is_date || text.get(10).is_some_and(char::is_ascii_digit) {
+        return None;
+    }
+    match text.get(10) {
+        Some('T') => Some(11 + time_len(&text[11..])?),
+        _ => Some(10),
+    }
+}
+
+/// Length of a time of day such as `14:30` or `9:05:30` at the start of
+/// `text`.
+pub fn time_len(text: &[char]) -> Option<usize> {
+    let hours = text.iter().take_while(|c| c.is_ascii_digit()).count();
+    let pair_at = |at: usize| {
+        text.get(at) == Some(&':')
+            && text
+                .get(at + 1..at + 3)
+                .is_some_and(|part| part.iter().all(char::is_ascii_digit))
+    };
+    if !(1..=2).contains(&hours) || !pair_at(hours) {
+        return None;
+    }
+    let len = if pair_at(hours + 3) {
+        hours + 6
+    } else {
+        hours + 3
+    };
+    (!text.get(len).is_some_and(char::is_ascii_digit)).then_some(len)
+}
@@ -0,0 +1,52 @@
+/// A named value substituted into the expression while parsing.
+pub struct Constant {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub value: f64,
+    pub help: &'static str,
+}
+
+pub const CONSTANTS: &[Constant] = &[
+    Constant {
+        name: "pi",
+        aliases: &["π"],
+        value: std::f64::consts::PI,
+        help: "ratio of a circle's circumference to its diameter",
+    },
+    Constant {
+        name: "tau",
+        aliases: &["τ"],
+        value: std::f64::consts::TAU,
+        help: "2 x pi",
+    },
+    Constant {
+        name: "e",
+        aliases: &[],
+        value: std::f64::consts::E,
+        help: "Euler's number, base of the natural logarithm",
+    },
+    Constant {
+        name: "phi",
+        aliases: &["φ"],
+        value: 1.618_033_988_749_895,
+        help: "golden ratio",
+    },
+    Constant {
+        name: "inf",
+        aliases: &["∞"],
+        value: f64::INFINITY,
+        help: "positive infinity",
+    },
+    Constant {
+        name: "nan",
+        aliases: &[],
+        value: f64::NAN,
+        help: "not a number",
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Constant> {
+    CONSTANTS
+        .iter()
+        .find(|c| c.name == name || c.aliases.contains(&name))
+}
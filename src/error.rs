@@ -0,0 +1,300 @@
+use std::{fmt, io};
+
+use crate::functions::Arity;
+
+/// A range of character columns in the input, `end` being exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// Everything that can go wrong between reading the command line and
+/// printing a value. Each variant has a stable code (see [`KalcError::code`])
+/// and belongs to a [`Category`] that decides the process exit status.
+#[derive(Debug)]
+pub enum KalcError {
+    /// A character or literal that is not part of kalc's syntax.
+    Lex(String),
+    /// Valid tokens that do not form an expression or statement.
+    Parse(String),
+    /// A function or operator applied outside the values it is defined for.
+    Domain(String),
+    DivisionByZero,
+    /// A result too large to represent, along with how to avoid the error
+    /// when there is a way.
+    Overflow(Option<String>),
+    UnknownIdentifier(String),
+    UnknownFunction(String),
+    ArityMismatch {
+        name: String,
+        expected: Arity,
+        got: usize,
+    },
+    RecursionLimit {
+        name: String,
+        limit: usize,
+    },
+    /// `ans` or `$n` referring to a result that does not exist yet.
+    MissingResult {
+        name: String,
+        available: usize,
+    },
+    /// Bad command line options.
+    Usage(String),
+    Io(io::Error),
+    /// An operation in exact mode whose result is not rational. Only an
+    /// error with `--strict`, a warning otherwise.
+    Inexact,
+    /// Quantities of different dimensions added, compared or converted,
+    /// each described by its SI unit, such as `N` or `m/s`.
+    DimensionMismatch {
+        left: String,
+        right: String,
+    },
+}
+
+/// Groups of errors sharing an exit status, so that scripts can tell bad
+/// input apart from undefined math without parsing stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Reading input or writing output failed.
+    Io,
+    /// The command line itself is wrong.
+    Usage,
+    /// The expression could not be read.
+    Syntax,
+    /// The expression refers to something that does not exist, or calls it
+    /// the wrong way.
+    Reference,
+    /// The expression is well formed but its value is undefined.
+    Math,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Io,
+        Category::Usage,
+        Category::Syntax,
+        Category::Reference,
+        Category::Math,
+    ];
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Category::Io => 1,
+            Category::Usage => 2,
+            Category::Syntax => 3,
+            Category::Reference => 4,
+            Category::Math => 5,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Category::Io => "reading input or writing output failed",
+            Category::Usage => "invalid command line options",
+            Category::Syntax => "the expression could not be parsed",
+            Category::Reference => "unknown name, wrong argument count or runaway recursion",
+            Category::Math => "division by zero, domain error, overflow or incompatible units",
+        }
+    }
+}
+
+impl KalcError {
+    /// A stable identifier for the kind of error, safe to match on in scripts.
+    pub fn code(&self) -> &'static str {
+        match self {
+            KalcError::Lex(_) => "E0001",
+            KalcError::Parse(_) => "E0002",
+            KalcError::Domain(_) => "E0003",
+            KalcError::DivisionByZero => "E0004",
+            KalcError::Overflow(_) => "E0005",
+            KalcError::UnknownIdentifier(_) => "E0006",
+            KalcError::UnknownFunction(_) => "E0007",
+            KalcError::ArityMismatch { .. } => "E0008",
+            KalcError::RecursionLimit { .. } => "E0009",
+            KalcError::MissingResult { .. } => "E0010",
+            KalcError::Usage(_) => "E0011",
+            KalcError::Io(_) => "E0012",
+            KalcError::Inexact => "E0013",
+            KalcError::DimensionMismatch { .. } => "E0014",
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            KalcError::Io(_) => Category::Io,
+            KalcError::Usage(_) => Category::Usage,
+            KalcError::Lex(_) | KalcError::Parse(_) => Category::Syntax,
+            KalcError::UnknownIdentifier(_)
+            | KalcError::UnknownFunction(_)
+            | KalcError::ArityMismatch { .. }
+            | KalcError::RecursionLimit { .. }
+            | KalcError::MissingResult { .. } => Category::Reference,
+            KalcError::Domain(_)
+            | KalcError::DivisionByZero
+            | KalcError::Overflow(_)
+            | KalcError::Inexact
+            | KalcError::DimensionMismatch { .. } => Category::Math,
+        }
+    }
+
+    /// Attaches the columns of the input responsible for the error.
+    pub fn at(self, span: Span) -> Error {
+        Error {
+            kind: self,
+            span: Some(span),
+        }
+    }
+}
+
+impl fmt::Display for KalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KalcError::Lex(message)
+            | KalcError::Parse(message)
+            | KalcError::Domain(message)
+            | KalcError::Usage(message) => f.write_str(message),
+            KalcError::DivisionByZero => f.write_str("Division by zero"),
+            KalcError::Overflow(Some(message)) => f.write_str(message),
+            KalcError::Overflow(None) => f.write_str("Result is too large to represent"),
+            KalcError::UnknownIdentifier(name) => write!(f, "Unknown identifier: {}", name),
+            KalcError::UnknownFunction(name) => write!(f, "Unknown function: {}", name),
+            KalcError::ArityMismatch {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Function '{}' expects {}, got {}",
+                name,
+                expected.describe(),
+                got
+            ),
+            KalcError::RecursionLimit { name, limit } => {
+                write!(
+                    f,
+                    "Maximum recursion depth ({}) exceeded in '{}'",
+                    limit, name
+                )
+            }
+            KalcError::MissingResult { name, .. } if name == "ans" => {
+                f.write_str("No previous result for 'ans'")
+            }
+            KalcError::MissingResult { name, available } => {
+                write!(f, "No result {} in history ({} so far)", name, available)
+            }
+            KalcError::Io(err) => write!(f, "{}", err),
+            KalcError::Inexact => f.write_str("No exact rational result"),
+            KalcError::DimensionMismatch { left, right } => {
+                write!(f, "Incompatible units: {} and {}", left, right)
+            }
+        }
+    }
+}
+
+/// A [`KalcError`] along with where in the input it happened, when that is
+/// known.
+#[derive(Debug)]
+pub struct Error {
+    pub kind: KalcError,
+    pub span: Option<Span>,
+}
+
+impl Error {
+    /// Moves the error to `span`, used when an error raised inside a user
+    /// function body has to be reported at the call site instead.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        self.kind.category().exit_code()
+    }
+
+    /// Formats the error followed by the line of `source` it refers to, with
+    /// the offending columns underlined:
+    ///
+    /// ```text
+    /// Error[E0006]: Unknown identifier: foo
+    ///   2 + foo x 3
+    ///       ^~~
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        self.render_as("Error", source)
+    }
+
+    /// Like [`Error::render`], for problems that did not stop evaluation.
+    pub fn render_warning(&self, source: &str) -> String {
+        self.render_as("Warning", source)
+    }
+
+    fn render_as(&self, label: &str, source: &str) -> String {
+        let header = format!("{}[{}]: {}", label, self.kind.code(), self.kind);
+        let Some(span) = self.span else {
+            return header;
+        };
+
+        // Locate the line holding the start of the span; spans of
+        // multi-line input are underlined up to the end of that line.
+        let mut line_start = 0;
+        let mut line = source;
+        for candidate in source.split('\n') {
+            let len = candidate.chars().count();
+            line = candidate;
+            if span.start <= line_start + len {
+                break;
+            }
+            line_start += len + 1;
+        }
+
+        let line_len = line.chars().count();
+        let column = span.start.saturating_sub(line_start).min(line_len);
+        let width = span
+            .end
+            .saturating_sub(span.start)
+            .min((line_len + 1).saturating_sub(column))
+            .max(1);
+
+        format!(
+            "{}\n  {}\n  {}^{}",
+            header,
+            line,
+            " ".repeat(column),
+            "~".repeat(width - 1)
+        )
+    }
+}
+
+impl From<KalcError> for Error {
+    fn from(kind: KalcError) -> Self {
+        Self { kind, span: None }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        KalcError::Io(err).into()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
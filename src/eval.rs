@@ -0,0 +1,381 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+use crate::{
+    error::{Error, KalcError, Span},
+    functions,
+    numeric::Numeric,
+    parser::{ASTNode, NodeKind, Op, Statement, UnaryOp},
+};
+
+/// How deeply user-defined functions may call each other before evaluation
+/// is aborted, keeping runaway recursion from overflowing the stack.
+const MAX_CALL_DEPTH: usize = 256;
+
+/// How deeply nodes may be evaluated inside one another, counting the
+/// bodies of every function being called. Bounds recursion into functions
+/// whose bodies are deeply nested, which [`MAX_CALL_DEPTH`] alone does not.
+const MAX_EVAL_DEPTH: usize = 1024;
+
+impl ASTNode {
+    pub fn eval<N: Numeric>(&self, scope: &Scope<N>) -> Result<N::Value, Error> {
+        let depth = &scope.env.eval_depth;
+        depth.set(depth.get() + 1);
+        let result = self.eval_node(scope);
+        depth.set(depth.get() - 1);
+        result
+    }
+
+    fn eval_node<N: Numeric>(&self, scope: &Scope<N>) -> Result<N::Value, Error> {
+        let backend = &scope.env.backend;
+        match &self.kind {
+            NodeKind::Literal(text) => backend
+                .parse(text)
+                .and_then(|result| scope.check(result, &[]))
+                .map_err(|err| err.at(self.span)),
+            NodeKind::Calendar(kind, text) => backend
+                .calendar(*kind, text)
+                .map_err(|err| err.at(self.span)),
+            NodeKind::Imaginary(text) => backend.imaginary(text).map_err(|err| err.at(self.span)),
+            NodeKind::Number(n) => backend
+                .float(*n)
+                .map_err(|err| err.at(self.span))
+                .and_then(|result| scope.exact(result, &[], self.span)),
+            // Variables shadow units, and unless it has been assigned, `i`
+            // is the imaginary unit for backends that have one.
+            NodeKind::Variable(name) => scope
+                .get(name)
+                .map(Ok)
+                .or_else(|| backend.named(name))
+                .or_else(|| {
+                    (name == "i")
+                        .then(|| backend.imaginary("1").ok().map(Ok))
+                        .flatten()
+                })
+                .unwrap_or_else(|| {
+                    Err(if is_reserved(name) {
+                        KalcError::MissingResult {
+                            name: name.clone(),
+                            available: scope.env.history.len(),
+                        }
+                    } else {
+                        KalcError::UnknownIdentifier(name.clone())
+                    })
+                })
+                .map_err(|err| err.at(self.span)),
+            NodeKind::UnaryOp { op, operand } => {
+                // Negative literals are read as one, so that -128 fits in a
+                // byte.
+                if let (UnaryOp::Neg, NodeKind::Literal(text)) = (op, &operand.kind) {
+                    return backend
+                        .parse(&format!("-{}", text))
+                        .and_then(|result| scope.check(result, &[]))
+                        .map_err(|err| err.at(self.span));
+                }
+                let operand = operand.eval(scope)?;
+                match op {
+                    UnaryOp::Neg => backend.neg(&operand).map_err(|err| err.at(self.span)),
+                    UnaryOp::Plus => Ok(operand),
+                    UnaryOp::BitNot => backend.bit_not(&operand).map_err(|err| err.at(self.span)),
+                    UnaryOp::Factorial => backend
+                        .factorial(&operand)
+                        .and_then(|result| scope.check(result, &[operand]))
+                        .map_err(|err| err.at(self.span)),
+                }
+            }
+            NodeKind::Call { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(scope))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match scope.env.function(name) {
+                    Some(function) => function.call(name, args, scope, self.span),
+                    None => functions::resolve(name, args.len())
+                        .and_then(|builtin| backend.call(builtin, &args, scope.env.ieee))
+                        .and_then(|result| scope.check(result, &args))
+                        .map_err(|err| err.at(self.span))
+                        .and_then(|result| scope.exact(result, &args, self.span)),
+                }
+            }
+            NodeKind::Conditional {
+                cond,
+                then,
+                otherwise,
+            } => {
+                if !backend.is_zero(&cond.eval(scope)?) {
+                    then.eval(scope)
+                } else {
+                    otherwise.eval(scope)
+                }
+            }
+            NodeKind::Convert {
+                value,
+                target,
+                label,
+            } => {
+                let value = value.eval(scope)?;
+                let target = target.eval(scope)?;
+                backend
+                    .convert(&value, &target, label)
+                    .map_err(|err| err.at(self.span))
+            }
+            NodeKind::Radix { value, radix } => {
+                let value = value.eval(scope)?;
+                backend
+                    .radix(&value, *radix)
+                    .map_err(|err| err.at(self.span))
+            }
+            NodeKind::BinaryOp { .. } => self.eval_chain(scope),
+        }
+    }
+
+    /// Evaluates a chain of operators such as `1 + 2 + 3` from the left
+    /// without recursing into it, since the chain nests as deep as it is long.
+    fn eval_chain<N: Numeric>(&self, scope: &Scope<N>) -> Result<N::Value, Error> {
+        let mut chain = vec![];
+        let mut first = self;
+        while let NodeKind::BinaryOp { left, .. } = &first.kind {
+            chain.push(first);
+            first = left;
+        }
+        let mut value = first.eval(scope)?;
+        for node in chain.into_iter().rev() {
+            value = node.eval_binary(value, scope)?;
+        }
+        Ok(value)
+    }
+
+    /// Applies the operator of a binary node to `left`, the value of its
+    /// left operand, and its right operand.
+    fn eval_binary<N: Numeric>(&self, left: N::Value, scope: &Scope<N>) -> Result<N::Value, Error> {
+        let backend = &scope.env.backend;
+        let NodeKind::BinaryOp { op, right, .. } = &self.kind else {
+            unreachable!("not a binary operator");
+        };
+        let divisor_span = right.span;
+        let right = right.eval(scope)?;
+        if !scope.env.ieee {
+            let divides = matches!(op, Op::Div | Op::FloorDiv | Op::Rem | Op::Mod);
+            // A negative power of zero is a division by zero as well.
+            let inverts_zero =
+                matches!(op, Op::Pow) && backend.is_zero(&left) && backend.is_negative(&right);
+            if (divides && backend.is_zero(&right)) || inverts_zero {
+                return Err(KalcError::DivisionByZero.at(divisor_span));
+            }
+        }
+        let operands = [left, right];
+        backend
+            .binary(op, &operands[0], &operands[1])
+            .and_then(|result| scope.check(result, &operands))
+            .map_err(|err| err.at(self.span))
+            .and_then(|result| scope.exact(result, &operands, self.span))
+    }
+}
+
+impl Statement {
+    /// Runs the statement against `env`, returning the value it produced.
+    /// Assignments produce the value that was assigned, definitions nothing.
+    pub fn execute<N: Numeric>(&self, env: &mut Environment<N>) -> Result<Option<N::Value>, Error> {
+        match self {
+            Statement::Assign { name, value } => {
+                let value = value.eval(&Scope::global(env))?;
+                env.set(name, value.clone());
+                Ok(Some(value))
+            }
+            Statement::Define { name, function } => {
+                env.define(name, function.clone());
+                Ok(None)
+            }
+            Statement::Expr(expr) => expr.eval(&Scope::global(env)).map(Some),
+        }
+    }
+}
+
+/// A function defined with `name(params) = body`.
+#[derive(Debug)]
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: ASTNode,
+}
+
+impl UserFunction {
+    /// Evaluates the body with `args` bound to the parameters. Errors are
+    /// reported at `call_span` since the body may come from an earlier input.
+    fn call<N: Numeric>(
+        &self,
+        name: &str,
+        args: Vec<N::Value>,
+        caller: &Scope<N>,
+        call_span: Span,
+    ) -> Result<N::Value, Error> {
+        if args.len() != self.params.len() {
+            return Err(KalcError::ArityMismatch {
+                name: name.to_string(),
+                expected: functions::Arity::Exact(self.params.len()),
+                got: args.len(),
+            }
+            .at(call_span));
+        }
+        if caller.depth >= MAX_CALL_DEPTH || caller.env.eval_depth.get() >= MAX_EVAL_DEPTH {
+            return Err(KalcError::RecursionLimit {
+                name: name.to_string(),
+                limit: MAX_CALL_DEPTH,
+            }
+            .at(call_span));
+        }
+
+        // The body sees its own parameters and the globals, never the
+        // caller's parameters.
+        let scope = Scope {
+            env: caller.env,
+            params: self.params.iter().cloned().zip(args).collect(),
+            depth: caller.depth + 1,
+        };
+        let fallback = caller.env.fallback.get();
+        let result = self.body.eval(&scope);
+        if fallback.is_none() && caller.env.fallback.get().is_some() {
+            caller.env.fallback.set(Some(call_span));
+        }
+        result.map_err(|err| err.with_span(call_span))
+    }
+}
+
+/// Variables and functions defined by earlier statements.
+#[derive(Debug)]
+pub struct Environment<N: Numeric> {
+    pub vars: HashMap<String, N::Value>,
+    pub functions: HashMap<String, Rc<UserFunction>>,
+    /// Every value produced so far, `$1` being the first and `ans` the last.
+    pub history: Vec<N::Value>,
+    /// How numbers are represented, fixed for the whole session.
+    pub backend: N,
+    /// Let infinities and NaNs through as IEEE-754 defines them instead of
+    /// reporting division by zero, domain errors and overflow.
+    pub ieee: bool,
+    /// Where exact mode first had to fall back to floats, reported as a
+    /// warning once the input has been evaluated.
+    fallback: Cell<Option<Span>>,
+    /// How many nodes are being evaluated inside one another.
+    eval_depth: Cell<usize>,
+}
+
+impl<N: Numeric> Environment<N> {
+    pub fn new(backend: N, ieee: bool) -> Self {
+        Self {
+            vars: HashMap::new(),
+            functions: HashMap::new(),
+            history: vec![],
+            backend,
+            ieee,
+            fallback: Cell::new(None),
+            eval_depth: Cell::new(0),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&N::Value> {
+        if name == "ans" {
+            return self.history.last();
+        }
+        if let Some(index) = name.strip_prefix('$') {
+            let index = index.parse::<usize>().ok()?.checked_sub(1)?;
+            return self.history.get(index);
+        }
+        self.vars.get(name)
+    }
+
+    /// Appends a result to the history, returning its `$n` number.
+    pub fn record(&mut self, value: N::Value) -> usize {
+        self.history.push(value);
+        self.history.len()
+    }
+
+    fn set(&mut self, name: &str, value: N::Value) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    fn function(&self, name: &str) -> Option<&Rc<UserFunction>> {
+        self.functions.get(name)
+    }
+
+    fn define(&mut self, name: &str, function: Rc<UserFunction>) {
+        self.functions.insert(name.to_string(), function);
+    }
+
+    /// Returns, and forgets, where exact mode last fell back to floats.
+    pub fn take_fallback(&self) -> Option<Span> {
+        self.fallback.take()
+    }
+
+    pub fn clear(&mut self) {
+        self.vars.clear();
+        self.functions.clear();
+        self.history.clear();
+    }
+}
+
+/// Names the evaluator resolves itself, which therefore cannot be assigned.
+pub fn is_reserved(name: &str) -> bool {
+    name == "ans" || name.starts_with('$')
+}
+
+/// Names visible while evaluating an expression: the parameters of the user
+/// function being called, if any, shadowing the global environment.
+pub struct Scope<'a, N: Numeric> {
+    env: &'a Environment<N>,
+    params: HashMap<String, N::Value>,
+    depth: usize,
+}
+
+impl<'a, N: Numeric> Scope<'a, N> {
+    fn global(env: &'a Environment<N>) -> Self {
+        Self {
+            env,
+            params: HashMap::new(),
+            depth: 0,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<N::Value> {
+        self.params
+            .get(name)
+            .or_else(|| self.env.get(name))
+            .cloned()
+    }
+
+    /// Rejects an infinite or NaN `result` computed from finite `operands`.
+    /// Operands that are already non-finite, such as the `inf` constant,
+    /// carry on as in IEEE-754.
+    fn check(&self, result: N::Value, operands: &[N::Value]) -> Result<N::Value, KalcError> {
+        let backend = &self.env.backend;
+        if self.env.ieee
+            || backend.is_finite(&result)
+            || operands.iter().any(|n| !backend.is_finite(n))
+        {
+            Ok(result)
+        } else if backend.is_nan(&result) {
+            Err(KalcError::Domain("Result is not a real number".to_string()))
+        } else {
+            Err(KalcError::Overflow(None))
+        }
+    }
+
+    /// Notes a float `result` computed from exact `operands` by a backend
+    /// promising exact results, or rejects it when the backend is strict.
+    fn exact(
+        &self,
+        result: N::Value,
+        operands: &[N::Value],
+        span: Span,
+    ) -> Result<N::Value, Error> {
+        let backend = &self.env.backend;
+        if !backend.fell_back(&result, operands) {
+            return Ok(result);
+        }
+        if backend.strict() {
+            return Err(KalcError::Inexact.at(span));
+        }
+        if self.env.fallback.get().is_none() {
+            self.env.fallback.set(Some(span));
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,290 @@
+use crate::error::KalcError;
+
+/// How many arguments a built-in accepts.
+#[derive(Debug, Clone, Copy)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    fn accepts(self, n: usize) -> bool {
+        match self {
+            Arity::Exact(k) => n == k,
+            Arity::Range(lo, hi) => (lo..=hi).contains(&n),
+            Arity::AtLeast(k) => n >= k,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        let plural = |k: usize| if k == 1 { "argument" } else { "arguments" };
+        match self {
+            Arity::Exact(k) => format!("{k} {}", plural(k)),
+            Arity::Range(lo, hi) => format!("{lo} to {hi} arguments"),
+            Arity::AtLeast(k) => format!("at least {k} {}", plural(k)),
+        }
+    }
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub help: &'static str,
+    func: fn(&[f64]) -> Result<f64, KalcError>,
+    domain: Option<Domain>,
+}
+
+/// The arguments a function is defined for over the reals. Outside of it
+/// IEEE-754 would return NaN or infinity.
+struct Domain {
+    /// Completes "Function 'sqrt' expects ...".
+    requirement: &'static str,
+    accepts: fn(&[f64]) -> bool,
+}
+
+macro_rules! unary {
+    ($name:literal, $help:literal, $f:expr) => {
+        Builtin {
+            name: $name,
+            arity: Arity::Exact(1),
+            help: $help,
+            func: |args| Ok($f(args[0])),
+            domain: None,
+        }
+    };
+    ($name:literal, $help:literal, $f:expr, $requirement:literal, $accepts:expr) => {
+        Builtin {
+            name: $name,
+            arity: Arity::Exact(1),
+            help: $help,
+            func: |args| Ok($f(args[0])),
+            domain: Some(Domain {
+                requirement: $requirement,
+                accepts: |args| $accepts(args[0]),
+            }),
+        }
+    };
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    unary!(
+        "sqrt",
+        "square root",
+        f64::sqrt,
+        "a non-negative argument",
+        |x: f64| x >= 0.0
+    ),
+    unary!("cbrt", "cube root", f64::cbrt),
+    unary!("sin", "sine (radians)", f64::sin),
+    unary!("cos", "cosine (radians)", f64::cos),
+    unary!("tan", "tangent (radians)", f64::tan),
+    unary!(
+        "asin",
+        "inverse sine",
+        f64::asin,
+        "an argument between -1 and 1",
+        |x: f64| x.abs() <= 1.0
+    ),
+    unary!(
+        "acos",
+        "inverse cosine",
+        f64::acos,
+        "an argument between -1 and 1",
+        |x: f64| x.abs() <= 1.0
+    ),
+    unary!("atan", "inverse tangent", f64::atan),
+    Builtin {
+        name: "atan2",
+        arity: Arity::Exact(2),
+        help: "atan2(y, x), angle of the point (x, y)",
+        func: |args| Ok(args[0].atan2(args[1])),
+        domain: None,
+    },
+    unary!("sinh", "hyperbolic sine", f64::sinh),
+    unary!("cosh", "hyperbolic cosine", f64::cosh),
+    unary!("tanh", "hyperbolic tangent", f64::tanh),
+    unary!("asinh", "inverse hyperbolic sine", f64::asinh),
+    unary!(
+        "acosh",
+        "inverse hyperbolic cosine",
+        f64::acosh,
+        "an argument of at least 1",
+        |x: f64| x >= 1.0
+    ),
+    unary!(
+        "atanh",
+        "inverse hyperbolic tangent",
+        f64::atanh,
+        "an argument strictly between -1 and 1",
+        |x: f64| x.abs() < 1.0
+    ),
+    unary!("exp", "e raised to the argument", f64::exp),
+    unary!(
+        "ln",
+        "natural logarithm",
+        f64::ln,
+        "a positive argument",
+        |x: f64| x > 0.0
+    ),
+    unary!(
+        "log10",
+        "base-10 logarithm",
+        f64::log10,
+        "a positive argument",
+        |x: f64| x > 0.0
+    ),
+    unary!(
+        "log2",
+        "base-2 logarithm",
+        f64::log2,
+        "a positive argument",
+        |x: f64| x > 0.0
+    ),
+    Builtin {
+        name: "log",
+        arity: Arity::Range(1, 2),
+        help: "log(x) is base 10, log(x, b) is base b",
+        func: |args| Ok(args[0].log(args.get(1).copied().unwrap_or(10.0))),
+        domain: Some(Domain {
+            requirement: "a positive argument and a positive base other than 1",
+            accepts: |args| args[0] > 0.0 && args.get(1).is_none_or(|&b| b > 0.0 && b != 1.0),
+        }),
+    },
+    unary!("floor", "round towards negative infinity", f64::floor),
+    unary!("ceil", "round towards positive infinity", f64::ceil),
+    unary!("round", "round half away from zero", f64::round),
+    unary!("trunc", "round towards zero", f64::trunc),
+    unary!("abs", "absolute value", f64::abs),
+    unary!("sign", "-1, 0 or 1 depending on the sign", sign),
+    unary!("re", "real part", |x| x),
+    unary!("im", "imaginary part", |_| 0.0),
+    unary!("arg", "angle of a complex number, in radians", |x: f64| {
+        0.0f64.atan2(x)
+    }),
+    unary!("conj", "complex conjugate", |x| x),
+    Builtin {
+        name: "min",
+        arity: Arity::AtLeast(1),
+        help: "smallest argument",
+        func: |args| Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        domain: None,
+    },
+    Builtin {
+        name: "max",
+        arity: Arity::AtLeast(1),
+        help: "largest argument",
+        func: |args| Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        domain: None,
+    },
+    Builtin {
+        name: "hypot",
+        arity: Arity::AtLeast(2),
+        help: "euclidean norm, sqrt(a^2 + b^2 + ...)",
+        func: |args| Ok(args.iter().copied().fold(0.0, f64::hypot)),
+        domain: None,
+    },
+    Builtin {
+        name: "gcd",
+        arity: Arity::AtLeast(2),
+        help: "greatest common divisor of integers",
+        func: |args| integers("gcd", args).map(|ns| ns.into_iter().fold(0, gcd) as f64),
+        domain: None,
+    },
+    Builtin {
+        name: "lcm",
+        arity: Arity::AtLeast(2),
+        help: "least common multiple of integers",
+        func: |args| {
+            integers("lcm", args)?
+                .into_iter()
+                .try_fold(1, lcm)
+                .map(|n| n as f64)
+                .ok_or(KalcError::Overflow(None))
+        },
+        domain: None,
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Looks up the built-in `name`, checking it accepts `argc` arguments.
+pub fn resolve(name: &str, argc: usize) -> Result<&'static Builtin, KalcError> {
+    let Some(builtin) = lookup(name) else {
+        return Err(KalcError::UnknownFunction(name.to_string()));
+    };
+    if !builtin.arity.accepts(argc) {
+        return Err(KalcError::ArityMismatch {
+            name: name.to_string(),
+            expected: builtin.arity,
+            got: argc,
+        });
+    }
+    Ok(builtin)
+}
+
+impl Builtin {
+    /// Unless `ieee` is set, rejects arguments outside the function's real
+    /// domain rather than letting it produce NaN or infinity.
+    pub fn check_domain(&self, args: &[f64], ieee: bool) -> Result<(), KalcError> {
+        // NaN arguments are let through: they can only come from the `nan`
+        // constant and are expected to propagate.
+        if let Some(domain) = &self.domain
+            && !ieee
+            && !args.iter().any(|n| n.is_nan())
+            && !(domain.accepts)(args)
+        {
+            return Err(KalcError::Domain(format!(
+                "Function '{}' expects {}",
+                self.name, domain.requirement
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn call(&self, args: &[f64]) -> Result<f64, KalcError> {
+        (self.func)(args)
+    }
+}
+
+fn sign(n: f64) -> f64 {
+    if n == 0.0 || n.is_nan() {
+        n
+    } else {
+        n.signum()
+    }
+}
+
+fn integers(name: &str, args: &[f64]) -> Result<Vec<u128>, KalcError> {
+    args.iter()
+        .map(|&n| {
+            if n.fract() != 0.0 || !n.is_finite() {
+                return Err(KalcError::Domain(format!(
+                    "Function '{}' expects integer arguments",
+                    name
+                )));
+            }
+            // `u128::MAX as f64` rounds up to 2^128, the first float that
+            // does not fit.
+            if n.abs() >= u128::MAX as f64 {
+                return Err(KalcError::Overflow(None));
+            }
+            Ok(n.abs() as u128)
+        })
+        .collect()
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// `None` when the result does not fit in a `u128`.
+fn lcm(a: u128, b: u128) -> Option<u128> {
+    if a == 0 || b == 0 {
+        Some(0)
+    } else {
+        (a / gcd(a, b)).checked_mul(b)
+    }
+}
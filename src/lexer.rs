@@ -0,0 +1,593 @@
+use std::{fmt, iter::Peekable, slice::Iter};
+
+use num_bigint::BigInt;
+
+use crate::{
+    calendar::{self, Calendar},
+    error::{Error, KalcError, Span},
+};
+
+#[derive(Debug, Clone)]
+pub enum Token {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Rem,
+    Mod,
+    Pow,
+    /// Postfix `!`, the factorial.
+    Bang,
+    LParen,
+    RParen,
+    Comma,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    /// `&`, bitwise and.
+    BitAnd,
+    /// `|`, bitwise or.
+    BitOr,
+    /// `^^` or `xor`, bitwise exclusive or, `^` being the power.
+    BitXor,
+    /// `~`, bitwise not.
+    BitNot,
+    /// `<<`.
+    Shl,
+    /// `>>`, which keeps the sign of signed integers.
+    Shr,
+    /// `>>>`, which shifts in zeros whatever the sign.
+    UShr,
+    Question,
+    Colon,
+    Assign,
+    Semicolon,
+    Let,
+    /// A number literal as written, converted once the evaluation mode is
+    /// known.
+    Number(String),
+    /// A number literal followed by `i`, such as `4i`, kept without the `i`.
+    Imaginary(String),
+    /// A date such as `2026-10-18`, possibly with a time of day as in
+    /// `2026-10-18T14:30`.
+    Date(String),
+    /// A time of day such as `14:30`.
+    Time(String),
+    /// A number directly followed by `w`, `d`, `h`, `m` or `s`, such as
+    /// `90d` or `20m`, kept without the unit.
+    Duration(String, char),
+    Ident(String),
+    /// Input the lexer could not make sense of. The error has already been
+    /// reported; the token only keeps the parser in step.
+    Invalid,
+    Eof,
+}
+
+impl Token {
+    /// Whether this token can close an operand, which makes a following `x` a
+    /// multiplication rather than the start of an identifier.
+    fn ends_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_)
+                | Token::Imaginary(_)
+                | Token::Date(_)
+                | Token::Time(_)
+                | Token::Duration(..)
+                | Token::Ident(_)
+                | Token::RParen
+                | Token::Bang
+        )
+    }
+
+    /// Whether an operand can begin with this token, a sign included.
+    pub fn starts_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_)
+                | Token::Imaginary(_)
+                | Token::Date(_)
+                | Token::Time(_)
+                | Token::Duration(..)
+                | Token::Ident(_)
+                | Token::LParen
+                | Token::Sub
+                | Token::Add
+                | Token::BitNot
+                | Token::Invalid
+        )
+    }
+
+    /// Whether this token combines the operands on either side of it.
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            Token::Add
+                | Token::Sub
+                | Token::Mul
+                | Token::Div
+                | Token::FloorDiv
+                | Token::Rem
+                | Token::Mod
+                | Token::Pow
+                | Token::Lt
+                | Token::Le
+                | Token::Gt
+                | Token::Ge
+                | Token::Eq
+                | Token::Ne
+                | Token::BitAnd
+                | Token::BitOr
+                | Token::BitXor
+                | Token::Shl
+                | Token::Shr
+                | Token::UShr
+                | Token::Question
+        )
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::Add => "+",
+            Token::Sub => "-",
+            Token::Mul => "x",
+            Token::Div => "/",
+            Token::FloorDiv => "//",
+            Token::Rem => "%",
+            Token::Mod => "mod",
+            Token::Pow => "^",
+            Token::Bang => "!",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::Comma => ",",
+            Token::Lt => "<",
+            Token::Le => "<=",
+            Token::Gt => ">",
+            Token::Ge => ">=",
+            Token::Eq => "==",
+            Token::Ne => "!=",
+            Token::BitAnd => "&",
+            Token::BitOr => "|",
+            Token::BitXor => "^^",
+            Token::BitNot => "~",
+            Token::Shl => "<<",
+            Token::Shr => ">>",
+            Token::UShr => ">>>",
+            Token::Question => "?",
+            Token::Colon => ":",
+            Token::Assign => "=",
+            Token::Semicolon => ";",
+            Token::Let => "let",
+            Token::Number(text) | Token::Date(text) | Token::Time(text) => text,
+            Token::Imaginary(text) => return write!(f, "{}i", text),
+            Token::Duration(text, unit) => return write!(f, "{}{}", text, unit),
+            Token::Ident(name) => name,
+            Token::Invalid => "invalid input",
+            Token::Eof => "end of input",
+        };
+        f.write_str(text)
+    }
+}
+
+/// A token along with the columns of the input it was read from.
+#[derive(Debug, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+fn lex_error(message: impl Into<String>, span: Span) -> Error {
+    KalcError::Lex(message.into()).at(span)
+}
+
+/// The letter the input continues with, when it makes up a whole word: the
+/// `i` of `4i` or the `h` of `3h`, but not the `i` of `2 in`.
+fn unit_letter(src: &Peekable<Iter<'_, char>>) -> Option<char> {
+    let mut ahead = src.clone();
+    let letter = *ahead.next()?;
+    ahead
+        .next()
+        .is_none_or(|c| !c.is_alphanumeric() && *c != '_')
+        .then_some(letter)
+}
+
+/// Whether the input continues with the rest of the word `xor`, which makes
+/// an `x` after an operand the operator rather than a multiplication.
+fn is_xor(src: &Peekable<Iter<'_, char>>) -> bool {
+    let mut ahead = src.clone();
+    ahead.next() == Some(&'o')
+        && ahead.next() == Some(&'r')
+        && ahead
+            .next()
+            .is_none_or(|c| !c.is_alphanumeric() && *c != '_')
+}
+
+/// The date or time of day the input holds from `first` on, if any, and
+/// how long it is. Times of day are not read in a conditional still missing
+/// its `:`, so that `c?5:10` keeps its branches.
+fn calendar_literal(
+    first: char,
+    src: &Peekable<Iter<'_, char>>,
+    in_conditional: bool,
+) -> Option<(String, Calendar)> {
+    let ahead = std::iter::once(first)
+        .chain(src.clone().take(24).copied())
+        .collect::<Vec<_>>();
+    let (len, kind) = match calendar::date_len(&ahead) {
+        Some(len) if len > 10 => (len, Calendar::DateTime),
+        Some(len) => (len, Calendar::Date),
+        None if in_conditional => return None,
+        None => (calendar::time_len(&ahead)?, Calendar::Time),
+    };
+    Some((ahead[..len].iter().collect(), kind))
+}
+
+/// Reads the rest of a number literal beginning with `first`: digits with
+/// `_` separators, at most one decimal point and an optional exponent, as in
+/// `6.022e23` or `1_000_000`, or a literal in another base such as `0xff`.
+/// Returns it the way the backends read numbers,
+/// without separators and with a digit on both sides of the point. The whole
+/// literal is consumed even when it is malformed, so that lexing resumes
+/// after it.
+fn number(first: char, src: &mut Peekable<Iter<'_, char>>, total: usize) -> Result<String, Error> {
+    let start = total - src.len() - 1;
+    if let Some(radix) = (first == '0').then(|| radix_prefix(src)).flatten() {
+        return prefixed_number(radix, src, start, total);
+    }
+
+    let mut digits = String::from(first);
+    let mut error = None;
+    while let Some(&&k) = src.peek() {
+        let at = total - src.len();
+        match k {
+            '0'..='9' => digits.push(k),
+            '.' if digits.contains('.') => {
+                let span = Span::new(at, at + 1);
+                error.get_or_insert(lex_error("Unexpected second decimal point in number", span));
+            }
+            '.' => digits.push(k),
+            '_' => {
+                let mut ahead = src.clone();
+                ahead.next();
+                let between_digits = digits.ends_with(|c: char| c.is_ascii_digit())
+                    && ahead.peek().is_some_and(|c| c.is_ascii_digit());
+                if !between_digits {
+                    let span = Span::new(at, at + 1);
+                    error.get_or_insert(lex_error(
+                        "Digit separators must stand between two digits",
+                        span,
+                    ));
+                }
+            }
+            _ => break,
+        }
+        src.next();
+    }
+    if digits.starts_with('.') {
+        digits.insert(0, '0');
+    }
+    if digits.ends_with('.') {
+        digits.push('0');
+    }
+
+    if let Some(&&e @ ('e' | 'E')) = src.peek() {
+        let mut ahead = src.clone();
+        ahead.next();
+        let sign = ahead.next_if(|c| matches!(c, '+' | '-')).copied();
+        match ahead.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                digits.push(e);
+                digits.extend(sign);
+                *src = ahead;
+                while let Some(k) = src.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(*k);
+                }
+            }
+            // A word such as the `eV` of `2eV`.
+            Some(c) if sign.is_none() && (c.is_alphanumeric() || **c == '_') => {}
+            _ => {
+                digits.push(e);
+                digits.extend(sign);
+                *src = ahead;
+                let span = Span::new(start, total - src.len());
+                error.get_or_insert(lex_error(
+                    format!("Missing digits in the exponent of {}", digits),
+                    span,
+                ));
+            }
+        }
+    }
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(digits),
+    }
+}
+
+/// The base a literal starting with `0` is written in when the input goes on
+/// with a prefix such as the `x` of `0xff`.
+fn radix_prefix(src: &Peekable<Iter<'_, char>>) -> Option<u32> {
+    let mut ahead = src.clone();
+    let radix = match ahead.next()? {
+        'x' | 'X' => 16,
+        'o' | 'O' => 8,
+        'b' | 'B' => 2,
+        _ => return None,
+    };
+    ahead
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        .then_some(radix)
+}
+
+/// Reads the rest of a literal such as `0xff`, `0o755` or `0b1011` after
+/// its `0`, returning its value in decimal.
+fn prefixed_number(
+    radix: u32,
+    src: &mut Peekable<Iter<'_, char>>,
+    start: usize,
+    total: usize,
+) -> Result<String, Error> {
+    let prefix = src.next().expect("checked by radix_prefix");
+    let mut digits = String::new();
+    let mut invalid = None;
+    while let Some(&k) = src.next_if(|c| c.is_ascii_alphanumeric() || **c == '_') {
+        if k == '_' {
+            continue;
+        }
+        if !k.is_digit(radix) {
+            invalid.get_or_insert(k);
+        }
+        digits.push(k);
+    }
+    let span = Span::new(start, total - src.len());
+    let name = match radix {
+        16 => "hexadecimal",
+        8 => "octal",
+        _ => "binary",
+    };
+    if let Some(k) = invalid {
+        return Err(lex_error(
+            format!(
+                "Invalid digit '{}' in {} number 0{}{}",
+                k, name, prefix, digits
+            ),
+            span,
+        ));
+    }
+    BigInt::parse_bytes(digits.as_bytes(), radix)
+        .map(|n| n.to_string())
+        .ok_or_else(|| lex_error(format!("Invalid number: 0{}{}", prefix, digits), span))
+}
+
+/// Splits the input into tokens. Malformed input does not stop the lexer: it
+/// is reported in the returned errors and stands in the token stream as
+/// [`Token::Invalid`], so the parser can still look for further problems.
+pub fn tokenize(mut src: Peekable<Iter<'_, char>>) -> (Vec<SpannedToken>, Vec<Error>) {
+    // Columns are derived from how much of the input is left to read.
+    let total = src.len();
+    let mut errors = vec![];
+    if total == 0 {
+        errors.push(lex_error("Invalid math expression", Span::new(0, 1)));
+    }
+
+    let mut tokens: Vec<SpannedToken> = vec![];
+    // Conditionals still waiting for their ':', inside which `1:30` is
+    // read as two branches rather than a time.
+    let mut open_conditionals = 0usize;
+    while let Some(n) = src.next() {
+        let start = total - src.len() - 1;
+        let token = match n {
+            '-' => Token::Sub,
+            '+' => Token::Add,
+            'x' if tokens.last().is_some_and(|t| t.token.ends_operand()) && !is_xor(&src) => {
+                Token::Mul
+            }
+            '*' => {
+                if src.peek() == Some(&&'*') {
+                    src.next();
+                    Token::Pow
+                } else {
+                    Token::Mul
+                }
+            }
+            '^' => {
+                if src.peek() == Some(&&'^') {
+                    src.next();
+                    Token::BitXor
+                } else {
+                    Token::Pow
+                }
+            }
+            '&' => Token::BitAnd,
+            '|' => Token::BitOr,
+            '~' => Token::BitNot,
+            '/' => {
+                if src.peek() == Some(&&'/') {
+                    src.next();
+                    Token::FloorDiv
+                } else {
+                    Token::Div
+                }
+            }
+            '%' => Token::Rem,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '=' => {
+                if src.peek() == Some(&&'=') {
+                    src.next();
+                    Token::Eq
+                } else {
+                    Token::Assign
+                }
+            }
+            '!' => {
+                if src.peek() == Some(&&'=') {
+                    src.next();
+                    Token::Ne
+                } else {
+                    Token::Bang
+                }
+            }
+            '<' => match src.next_if(|&&k| k == '=' || k == '<') {
+                Some('=') => Token::Le,
+                Some(_) => Token::Shl,
+                None => Token::Lt,
+            },
+            '>' => match src.next_if(|&&k| k == '=' || k == '>') {
+                Some('=') => Token::Ge,
+                Some(_) if src.next_if_eq(&&'>').is_some() => Token::UShr,
+                Some(_) => Token::Shr,
+                None => Token::Gt,
+            },
+            '?' => {
+                open_conditionals += 1;
+                Token::Question
+            }
+            ':' => {
+                open_conditionals = open_conditionals.saturating_sub(1);
+                Token::Colon
+            }
+            ';' => {
+                open_conditionals = 0;
+                Token::Semicolon
+            }
+            '$' => {
+                let mut name = String::from('$');
+                while let Some(&&k) = src.peek() {
+                    if !k.is_ascii_digit() {
+                        break;
+                    }
+                    name.push(k);
+                    src.next();
+                }
+                if name.len() == 1 {
+                    let span = Span::new(start, start + 1);
+                    errors.push(lex_error("Expected a result number after '$'", span));
+                    Token::Invalid
+                } else {
+                    Token::Ident(name)
+                }
+            }
+            '0'..='9' if calendar_literal(*n, &src, open_conditionals > 0).is_some() => {
+                let (text, kind) = calendar_literal(*n, &src, open_conditionals > 0)
+                    .expect("checked by the guard");
+                src.nth(text.len() - 2);
+                if calendar::timestamp(kind, &text).is_none() {
+                    let span = Span::new(start, total - src.len());
+                    errors.push(lex_error(format!("Invalid date or time: {}", text), span));
+                    Token::Invalid
+                } else if kind == Calendar::Time {
+                    Token::Time(text)
+                } else {
+                    Token::Date(text)
+                }
+            }
+            c if c.is_ascii_digit()
+                || (*c == '.' && src.peek().is_some_and(|d| d.is_ascii_digit())) =>
+            {
+                match number(*c, &mut src, total) {
+                    Ok(digits) => match unit_letter(&src) {
+                        Some('i') => {
+                            src.next();
+                            Token::Imaginary(digits)
+                        }
+                        Some(unit) if calendar::duration_unit(unit).is_some() => {
+                            src.next();
+                            Token::Duration(digits, unit)
+                        }
+                        _ => Token::Number(digits),
+                    },
+                    Err(error) => {
+                        errors.push(error);
+                        Token::Invalid
+                    }
+                }
+            }
+            c if c.is_alphabetic() || *c == '∞' || *c == '°' => {
+                let mut word = String::from(*n);
+                while let Some(&&k) = src.peek() {
+                    if !k.is_alphanumeric() && k != '_' {
+                        break;
+                    }
+                    word.push(k);
+                    src.next();
+                }
+                match word.as_str() {
+                    "mod" => Token::Mod,
+                    "xor" => Token::BitXor,
+                    "let" => Token::Let,
+                    _ => Token::Ident(word),
+                }
+            }
+            ' ' | '\n' => continue,
+            _ => {
+                let span = Span::new(start, start + 1);
+                errors.push(lex_error(format!("Unrecognized character: {}", n), span));
+                Token::Invalid
+            }
+        };
+
+        tokens.push(SpannedToken {
+            token,
+            span: Span::new(start, total - src.len()),
+        });
+    }
+
+    tokens.push(SpannedToken {
+        token: Token::Eof,
+        span: Span::new(total, total + 1),
+    });
+    (tokens, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Token> {
+        let chars = input.chars().collect::<Vec<_>>();
+        let (tokens, errors) = tokenize(chars.iter().peekable());
+        assert!(errors.is_empty(), "{:?}", errors);
+        tokens.into_iter().map(|t| t.token).collect()
+    }
+
+    #[test]
+    fn conditional_branches_are_not_times() {
+        assert!(matches!(
+            tokens("c?5:10").as_slice(),
+            [
+                Token::Ident(_),
+                Token::Question,
+                Token::Number(a),
+                Token::Colon,
+                Token::Number(b),
+                Token::Eof,
+            ] if a == "5" && b == "10"
+        ));
+        assert!(matches!(
+            tokens("1>0?a:b?2:30").as_slice(),
+            [.., Token::Number(a), Token::Colon, Token::Number(b), Token::Eof]
+                if a == "2" && b == "30"
+        ));
+    }
+
+    #[test]
+    fn times_outside_conditionals() {
+        assert!(matches!(
+            tokens("12:30 + 1h").as_slice(),
+            [Token::Time(t), ..] if t == "12:30"
+        ));
+        assert!(matches!(
+            tokens("c ? 1 : 2; 9:15").as_slice(),
+            [.., Token::Time(t), Token::Eof] if t == "9:15"
+        ));
+    }
+}
@@ -1,266 +1,574 @@
-use anyhow::{Result, anyhow};
-use std::{env::args, io::stdin, iter::Peekable, slice::Iter};
+mod calendar;
+mod constants;
+mod error;
+mod eval;
+mod functions;
+mod lexer;
+mod numeric;
+mod parser;
+mod rates;
+mod units;
+
+use std::{
+    env::{self, args},
+    fmt::Display,
+    io::{IsTerminal, Write, stdin, stdout},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use bigdecimal::RoundingMode;
+use error::{Category, Error, KalcError, Span};
+use eval::Environment;
+use lexer::tokenize;
+use numeric::{Complex, Decimal, Exact, Fixed, Float, Format, Integer, Numeric, Units};
+use parser::Parser;
+use rates::Rates;
 
 const VERSION: &str = "0.1.2";
 
-#[derive(Debug, Clone)]
-enum Token {
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Number(f64),
-    Eof,
+fn print_constants() {
+    for constant in constants::CONSTANTS {
+        let mut names = vec![constant.name];
+        names.extend(constant.aliases);
+        println!(
+            "  {:<8} {:<20} {}",
+            names.join(", "),
+            Float.format(&constant.value, Format::default()),
+            constant.help
+        );
+    }
 }
 
-fn tokenize<'a>(mut src: Peekable<Iter<'a, char>>) -> Result<Vec<Token>> {
-    let Some(n) = src.peek() else {
-        return Err(anyhow!("Invalid math expression"));
-    };
-    if !n.is_numeric() {
-        return Err(anyhow!("Invalid math expression"));
-    };
-
-    let mut tokens = vec![];
-    while let Some(n) = src.next() {
-        match n {
-            '-' => tokens.push(Token::Sub),
-            '+' => tokens.push(Token::Add),
-            'x' => tokens.push(Token::Mul),
-            '/' => tokens.push(Token::Div),
-            '0'..='9' => {
-                let mut digits = String::from(*n);
-                let mut has_decimal = false;
-                while let Some(&&k) = src.peek() {
-                    if !k.is_numeric() {
-                        if k == '.' {
-                            if has_decimal {
-                                return Err(anyhow!("Invalid math expression"));
-                            }
-
-                            has_decimal = true;
-                        } else {
-                            break;
-                        }
-                    }
-                    digits.push(k);
-                    src.next();
-                }
-                tokens.push(Token::Number(digits.parse::<f64>()?));
-            }
-            ' ' | '\n' => continue,
-            _ => return Err(anyhow!("Unrecognized character: {}", n)),
-        }
+fn print_units() {
+    for unit in units::UNITS {
+        let mut names = vec![unit.name];
+        names.extend(unit.aliases);
+        let prefixes = if unit.prefixable { ", SI prefixes" } else { "" };
+        println!(
+            "  {:<22} {:<10} {}{}",
+            names.join(", "),
+            unit.dimension.unit_name(),
+            unit.help,
+            prefixes
+        );
     }
-
-    tokens.push(Token::Eof);
-    Ok(tokens)
 }
 
-#[derive(Debug, Clone)]
-enum Op {
-    Mul,
-    Add,
-    Sub,
-    Div,
+fn print_repl_commands() {
+    println!("  :help    Display this help message");
+    println!("  :vars    List the variables and functions defined so far");
+    println!("  :clear   Forget every variable and function");
+    println!("  :quit    Leave the session (Ctrl-D works too)");
 }
 
-#[derive(Debug, Clone)]
-enum ASTNode {
-    Number(f64),
-    BinaryOp {
-        left: Box<ASTNode>,
-        op: Op,
-        right: Box<ASTNode>,
-    },
-}
+fn print_help() {
+    println!("kalc-cli");
+    println!();
 
-impl ASTNode {
-    fn eval(&self) -> f64 {
-        match self {
-            ASTNode::Number(n) => *n,
-            ASTNode::BinaryOp { left, op, right } => {
-                let left = left.eval();
-                let right = right.eval();
-                match op {
-                    Op::Mul => left * right,
-                    Op::Add => left + right,
-                    Op::Sub => left - right,
-                    Op::Div => left / right,
-                }
-            }
-        }
-    }
-}
+    println!("USAGE:");
+    println!("  kalc [OPTIONS] [EXPRESSION]");
+    println!();
 
-#[derive(Debug)]
-struct Parser<'a> {
-    tokens: Peekable<Iter<'a, Token>>,
-}
+    println!("OPTIONS:");
+    println!("  -h, --help          Display this help message");
+    println!("  -v, --version       Display version information");
+    println!("  --list-constants    List the named constants");
+    println!("  --list-units        List the units of measurement");
+    println!("  --ieee              Return inf and nan as IEEE-754 does instead of failing");
+    println!("  --float             Compute with binary floats only, integers included");
+    println!("  --decimal           Compute with decimals instead of binary floats");
+    println!(
+        "  --precision N       Keep N significant digits in decimal mode (default {})",
+        numeric::DEFAULT_PRECISION
+    );
+    println!("  --rounding MODE     Round decimals half-even (default), half-up, half-down,");
+    println!("                      up, down, ceiling or floor");
+    println!("  --exact             Compute with exact fractions instead of binary floats");
+    println!("  --strict            Like --exact, but fail when a result is not rational");
+    println!("  --mixed             Like --exact, printing fractions above one as 1 1/4");
+    println!("  --complex           Compute with complex numbers, so that sqrt(-1) is i");
+    println!("  --polar             Like --complex, printing results as magnitude∠degrees");
+    println!("  --int WIDTH         Compute with integers of 8, 16, 32, 64 or 128 bits,");
+    println!("                      unsigned when written u32; results out of range fail");
+    println!("  --wrap              With --int, wrap results out of range around instead");
+    println!("  --hex, --oct, --bin Print integer results in base 16, 8 or 2");
+    println!("  --base N            Print integer results in base N, from 2 to 36");
+    println!("  --all-bases         Print integer results in decimal, hex and binary");
+    println!("  --rates FILE        Read exchange rates from a CSV or JSON file (default");
+    println!("                      ${})", rates::RATES_VAR);
+    println!("  --                  Treat every following argument as part of the expression");
+    println!();
 
-impl<'a> Parser<'a> {
-    fn new(tokens: Peekable<Iter<'a, Token>>) -> Self {
-        Self { tokens }
-    }
+    println!("EXPRESSION SYNTAX:");
+    println!("  Basic arithmetic: +, -, x (or *), /");
+    println!("  Floor division: a // b rounds the quotient down (-7 // 2 = -4)");
+    println!("  Remainder: a % b takes the sign of a (-7 % 3 = -1)");
+    println!("  Modulo: a mod b is always non-negative (-7 mod 3 = 2)");
+    println!("  Exponentiation: ^ or ** (right associative, 2^3^2 = 512)");
+    println!("  Factorial: 5! = 120, binding tighter than ^ and unary minus");
+    println!("  Grouping: ( and ) override precedence");
+    println!("  Function calls: sqrt(2), log(8, 2), max(1, 2, 3)");
+    println!("  Constants: 2 x pi, e^2");
+    println!("  Variables: r = 3; let area = pi x r^2; area");
+    println!("  Statements are separated by ';', the last value is printed");
+    println!("  Functions: f(x, y) = x^2 + y; f(3, 1)");
+    println!("  Comparisons: <, <=, >, >=, ==, != evaluate to 1 or 0");
+    println!("  Conditionals: cond ? a : b");
+    println!("  History: ans is the last result, $1, $2, ... the results so far");
+    println!("  Unary minus and plus: -5, 2 x -3, -(1 + 2)");
+    println!("  Imaginary numbers with --complex: 3 + 4i, i^2, sqrt(-4)");
+    println!("  Units after a number: 3 km + 200 m, 9.81 m/s^2, 2 kW");
+    println!("  Unit conversion: 60 mph to m/s, 5 kg x 9.81 m/s^2 in N");
+    println!("  Currencies with --rates: 100 USD to EUR, 20 EUR/h x 8 h");
+    println!("  Dates and times: 2026-10-18 + 45d, 2026-12-25 - today, 14:30 - 9:15, now");
+    println!("  Durations: 90d, 3h 20m + 45m in minutes (w, d, h, m and s)");
+    println!("  Numbers: 42, 3.14, .5, 5., 6.022e23, 1E-9, 1_000_000");
+    println!("  Other bases: 0xff, 0o755, 0b1011; 255 to hex, to oct, to bin, to base 36");
+    println!("  Bitwise with --int: a & b, a | b, a ^^ b (or xor), ~a, a << b, a >> b, a >>> b");
+    println!("  Integer arithmetic is exact at any size while it stays integral: 2^200, 30!");
+    println!();
 
-    fn peek(&mut self) -> Option<&Token> {
-        self.tokens.peek().copied()
+    println!("FUNCTIONS:");
+    for builtin in functions::BUILTINS {
+        println!("  {:<8} {}", builtin.name, builtin.help);
     }
+    println!();
 
-    fn advance(&mut self) -> Option<&Token> {
-        self.tokens.next()
-    }
+    println!("CONSTANTS:");
+    print_constants();
+    println!();
 
-    fn parse_program(&mut self) -> Result<Option<ASTNode>> {
-        self.parse_additive()
-    }
+    println!("EXAMPLES:");
+    println!("  kalc 2 + 3 * 4");
+    println!("  kalc 5 + 3 / 2");
+    println!("  kalc 3.14 * 2.5");
+    println!("  kalc \"(2 + 3) x 4\"");
+    println!("  kalc -5 + 3");
+    println!("  kalc 1.05 ^ 12");
+    println!("  kalc 2^200 + 30!");
+    println!("  kalc \"hypot(3, 4) + log(8, 2)\"");
+    println!("  kalc \"r = 3; area = pi x r^2; area\"");
+    println!("  kalc \"fact(n) = n <= 1 ? 1 : n x fact(n - 1); fact(10)\"");
+    println!("  kalc '2 + 3; ans x 4; $1 + $2'");
+    println!("  kalc --decimal 0.1 + 0.2");
+    println!("  kalc --precision 50 --rounding half-up 2 / 3");
+    println!("  kalc --exact 1/3 x 3");
+    println!("  kalc --exact --mixed 1/4 + 1");
+    println!("  kalc --complex \"(3 + 4i) x (1 - 2i)\"");
+    println!("  kalc --polar \"230 / (50 + 30i)\"");
+    println!("  kalc \"6 ft + 2 in to cm\"");
+    println!("  kalc --rates rates.json 100 USD to EUR");
+    println!("  kalc 2026-12-25 - today");
+    println!("  kalc --hex \"0xff00 + 0b1010\"");
+    println!("  kalc --int u32 --all-bases \"0xdeadbeef >>> 16 & 0xff\"");
+    println!("  kalc -- --5");
+    println!();
 
-    fn parse_additive(&mut self) -> Result<Option<ASTNode>> {
-        let Some(mut expr) = self.parse_multiplicative()? else {
-            return Ok(None);
-        };
-
-        while let Some(token) = self.peek() {
-            let op = match token {
-                Token::Add => Op::Add,
-                Token::Sub => Op::Sub,
-                Token::Number(_) => return Err(anyhow!("Invalid math expression")),
-                _ => break,
-            };
-            self.advance();
-
-            if let Some(right) = self.parse_multiplicative()? {
-                expr = ASTNode::BinaryOp {
-                    left: Box::new(expr),
-                    op,
-                    right: Box::new(right),
-                };
-            } else {
-                break;
-            }
-        }
+    println!("NOTES:");
+    println!("  - If no expression is provided, kalc starts an interactive session");
+    println!("    where variables and functions persist between lines");
+    println!("  - Every syntax error in an expression is reported, not just the first");
+    println!("  - Division by zero, square roots and logarithms of negative numbers and");
+    println!("    results too large to represent are errors unless --ieee is given");
+    println!("  - In decimal mode 0.1 + 0.2 is exactly 0.3. Arithmetic, integer powers,");
+    println!("    sqrt, cbrt, exp and rounding are exact to the precision; constants and");
+    println!("    other functions are computed as floats and then converted");
+    println!("  - In exact mode results are fractions such as 7/12. Powers and roots");
+    println!("    without a rational result, constants and other functions are computed");
+    println!("    as floats with a warning, or rejected with --strict");
+    println!("  - In complex mode functions outside their real domain return their");
+    println!("    principal complex value; --polar angles are in degrees");
+    println!("  - Quantities can only be added, compared or converted when their units");
+    println!("    measure the same thing; variables shadow units of the same name");
+    println!("  - A unit after a number applies to the whole term when it holds only");
+    println!("    numbers, so 1/2 mi is half a mile; 10 m / 2 s is still 5 m/s");
+    println!("  - °C and °F count from their own zero, which `to` accounts for:");
+    println!("    100 °C to °F is 212 °F. C and F are coulomb and farad");
+    println!("  - Amounts in different currencies must be converted before they are");
+    println!("    added, multiplied or divided. Rates files hold currency,rate,date rows");
+    println!("    or a JSON object with date, base and rates, every rate being per unit");
+    println!("    of the base currency; nothing is fetched over the network");
+    println!("  - A letter right after a number makes a duration: 20m is 20 minutes while");
+    println!("    20 m is 20 meters. Dates and times are local, from the system time zone");
+    println!("  - Only integers are written in other bases; anything else stays decimal");
+    println!("  - With --int, bitwise operators bind more loosely than comparisons and");
+    println!("    shifts more loosely than sums, as in C. Negative integers are written");
+    println!("    in two's complement in other bases, and >> keeps the sign while >>>");
+    println!("    shifts in zeros");
+    println!();
 
-        Ok(Some(expr))
+    println!("SESSION COMMANDS:");
+    print_repl_commands();
+    println!();
+
+    println!("EXIT STATUS:");
+    println!("  0   success");
+    for category in Category::ALL {
+        println!("  {}   {}", category.exit_code(), category.describe());
     }
+    println!("  Error messages start with a stable code such as Error[E0004]");
+    println!();
+
+    println!("VERSION:");
+    println!("  kalc-cli {VERSION}");
+}
 
-    fn parse_multiplicative(&mut self) -> Result<Option<ASTNode>> {
-        let Some(mut expr) = self.parse_primary_exp()? else {
-            return Ok(None);
-        };
-
-        while let Some(token) = self.peek() {
-            let op = match token {
-                Token::Mul => Op::Mul,
-                Token::Div => Op::Div,
-                Token::Number(_) => return Err(anyhow!("Invalid math expression")),
-                _ => break,
-            };
-            self.advance();
-
-            if let Some(right) = self.parse_primary_exp()? {
-                expr = ASTNode::BinaryOp {
-                    left: Box::new(expr),
-                    op,
-                    right: Box::new(right),
-                };
-            } else {
+/// Returns true for arguments shaped like long options (`--help`) as opposed
+/// to expressions that merely start with a minus sign (`-5`, `-pi`,
+/// `-sqrt(4)`). The short options `-h` and `-v` are matched by name.
+fn is_option(arg: &str) -> bool {
+    arg.starts_with("--")
+}
+
+fn main() -> ExitCode {
+    let mut args = args().skip(1);
+    let mut expr_args: Vec<String> = vec![];
+    let mut ieee = false;
+    let mut float = false;
+    let mut decimal = false;
+    let mut precision = numeric::DEFAULT_PRECISION;
+    let mut rounding = RoundingMode::HalfEven;
+    let mut exact = None;
+    let mut complex = false;
+    let mut int = None;
+    let mut wrap = false;
+    let mut format = Format::default();
+    let mut rates_path = None;
+
+    while let Some(arg) = args.next() {
+        if !expr_args.is_empty() {
+            expr_args.push(arg);
+            continue;
+        }
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return ExitCode::SUCCESS;
+            }
+            "-v" | "--version" => {
+                println!("kalc {VERSION}");
+                return ExitCode::SUCCESS;
+            }
+            "--list-constants" => {
+                print_constants();
+                return ExitCode::SUCCESS;
+            }
+            "--list-units" => {
+                print_units();
+                return ExitCode::SUCCESS;
+            }
+            "--ieee" => ieee = true,
+            "--float" => float = true,
+            "--decimal" => decimal = true,
+            "--exact" => {
+                exact.get_or_insert(false);
+            }
+            "--strict" => exact = Some(true),
+            "--mixed" => {
+                exact.get_or_insert(false);
+                format.mixed = true;
+            }
+            "--complex" => complex = true,
+            "--polar" => {
+                complex = true;
+                format.polar = true;
+            }
+            "--precision" => {
+                decimal = true;
+                match option_value(&arg, args.next(), parse_precision) {
+                    Ok(digits) => precision = digits,
+                    Err(err) => return report(&[err], ""),
+                }
+            }
+            "--int" => match option_value(&arg, args.next(), parse_int) {
+                Ok(width) => int = Some(width),
+                Err(err) => return report(&[err], ""),
+            },
+            "--wrap" => wrap = true,
+            "--all-bases" => format.all_bases = true,
+            "--hex" => format.radix = Some(16),
+            "--oct" => format.radix = Some(8),
+            "--bin" => format.radix = Some(2),
+            "--base" => match option_value(&arg, args.next(), parse_base) {
+                Ok(radix) => format.radix = Some(radix),
+                Err(err) => return report(&[err], ""),
+            },
+            "--rates" => {
+                match option_value(&arg, args.next(), |path| Ok::<_, &str>(PathBuf::from(path))) {
+                    Ok(path) => rates_path = Some(path),
+                    Err(err) => return report(&[err], ""),
+                }
+            }
+            "--rounding" => {
+                decimal = true;
+                match option_value(&arg, args.next(), parse_rounding) {
+                    Ok(mode) => rounding = mode,
+                    Err(err) => return report(&[err], ""),
+                }
+            }
+            "--" => {
+                expr_args.extend(args);
                 break;
             }
+            _ if is_option(&arg) => {
+                let err = KalcError::Usage(format!("Unknown option: {}", arg));
+                return report(&[err.into()], "");
+            }
+            _ => expr_args.push(arg),
         }
-
-        Ok(Some(expr))
     }
 
-    fn parse_primary_exp(&mut self) -> Result<Option<ASTNode>> {
-        let Some(Token::Number(n)) = self.peek() else {
-            return Ok(None);
-        };
-        let n = *n;
-        Ok(self.advance().map(|_| ASTNode::Number(n)))
+    let expr = (!expr_args.is_empty()).then(|| expr_args.join(" "));
+    if [float, decimal, exact.is_some(), complex, int.is_some()]
+        .into_iter()
+        .filter(|&chosen| chosen)
+        .count()
+        > 1
+    {
+        let err = KalcError::Usage(
+            "Only one of --float, --decimal, --exact, --complex and --int can be given".to_string(),
+        );
+        return report(&[err.into()], "");
     }
-}
+    if wrap && int.is_none() {
+        let err = KalcError::Usage("Option --wrap needs --int".to_string());
+        return report(&[err.into()], "");
+    }
+
+    // Without --rates, the file named by the environment is used.
+    let rates_path = rates_path.or_else(|| env::var_os(rates::RATES_VAR).map(PathBuf::from));
+    let rates = match rates_path.map(|path| Rates::load(&path)).transpose() {
+        Ok(rates) => rates,
+        Err(err) => return report(&[err.into()], ""),
+    };
 
-fn format_float(num: f64) -> String {
-    if num.fract() == 0.0 {
-        format!("{:.0}", num)
+    if float {
+        run(Units::new(Float, rates), ieee, expr, format)
+    } else if decimal {
+        let backend = Decimal::new(precision, rounding).expect("precision is positive");
+        run(Units::new(backend, rates), ieee, expr, format)
+    } else if let Some(strict) = exact {
+        run(Units::new(Exact { strict }, rates), ieee, expr, format)
+    } else if complex {
+        run(Units::new(Complex, rates), ieee, expr, format)
+    } else if let Some((bits, signed)) = int {
+        run(
+            Units::new(Fixed::new(bits, signed, wrap), rates),
+            ieee,
+            expr,
+            format,
+        )
     } else {
-        format!("{}", num)
+        run(Units::new(Integer, rates), ieee, expr, format)
     }
 }
 
-fn print_help() {
-    println!("kalc-cli");
-    println!();
+/// Evaluates `expr`, or starts an interactive session when there is none.
+fn run<N: Numeric>(backend: N, ieee: bool, expr: Option<String>, format: Format) -> ExitCode {
+    let mut env = Environment::new(backend, ieee);
+    let Some(expr) = expr else {
+        return repl(env, format).unwrap_or_else(|err| report(&[err], ""));
+    };
 
-    println!("USAGE:");
-    println!("  kalc [OPTIONS] [EXPRESSION]");
-    println!();
+    match evaluate(&expr, &mut env) {
+        Ok(Some((_, result))) => println!("{}", env.backend.format(&result, format)),
+        Ok(None) => {}
+        Err(errors) => return report(&errors, &expr),
+    }
 
-    println!("OPTIONS:");
-    println!("  -h, --help     Display this help message");
-    println!("  -v, --version  Display version information");
-    println!();
+    ExitCode::SUCCESS
+}
 
-    println!("EXPRESSION SYNTAX:");
-    println!("  Basic arithmetic: +, -, x, /");
-    println!("  Numbers can be integers or decimals");
-    println!();
+/// Parses the value following the option `name` with `parse`, which
+/// describes what it expected when the value is not acceptable.
+fn option_value<T, E: Display>(
+    name: &str,
+    value: Option<String>,
+    parse: fn(&str) -> Result<T, E>,
+) -> Result<T, Error> {
+    let value =
+        value.ok_or_else(|| KalcError::Usage(format!("Option {} expects a value", name)))?;
+    parse(&value).map_err(|expected| {
+        KalcError::Usage(format!(
+            "Invalid value '{}' for {}: expected {}",
+            value, name, expected
+        ))
+        .into()
+    })
+}
 
-    println!("EXAMPLES:");
-    println!("  kalc 2 + 3 * 4");
-    println!("  kalc 5 + 3 / 2");
-    println!("  kalc 3.14 * 2.5");
-    println!();
+fn parse_precision(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(digits) if (1..=numeric::MAX_PRECISION).contains(&digits) => Ok(digits),
+        _ => Err(format!(
+            "a number of digits from 1 to {}",
+            numeric::MAX_PRECISION
+        )),
+    }
+}
 
-    println!("NOTES:");
-    println!("  - If no expression is provided, kalcwill read from stdin");
-    println!();
+/// A width such as `32`, `i32` or `u32`, and whether it is signed.
+fn parse_int(value: &str) -> Result<(u32, bool), &'static str> {
+    let (bits, signed) = match value.strip_prefix('u') {
+        Some(bits) => (bits, false),
+        None => (value.strip_prefix('i').unwrap_or(value), true),
+    };
+    match bits.parse::<u32>() {
+        Ok(bits) if numeric::WIDTHS.contains(&bits) => Ok((bits, signed)),
+        _ => Err("8, 16, 32, 64 or 128 bits, as in 32, i32 or u32"),
+    }
+}
 
-    println!("VERSION:");
-    println!("  kalc-cli {VERSION}");
+fn parse_base(value: &str) -> Result<u32, &'static str> {
+    match value.parse::<u32>() {
+        Ok(radix) if (2..=36).contains(&radix) => Ok(radix),
+        _ => Err("a base from 2 to 36"),
+    }
+}
+
+fn parse_rounding(value: &str) -> Result<RoundingMode, &'static str> {
+    Ok(match value {
+        "half-even" => RoundingMode::HalfEven,
+        "half-up" => RoundingMode::HalfUp,
+        "half-down" => RoundingMode::HalfDown,
+        "up" => RoundingMode::Up,
+        "down" => RoundingMode::Down,
+        "ceiling" => RoundingMode::Ceiling,
+        "floor" => RoundingMode::Floor,
+        _ => return Err("half-even, half-up, half-down, up, down, ceiling or floor"),
+    })
+}
+
+/// Prints `errors` against the input they came from and returns the exit
+/// status for the category of the first one.
+fn report(errors: &[Error], source: &str) -> ExitCode {
+    for err in errors {
+        eprintln!("{}", err.render(source));
+    }
+    errors
+        .first()
+        .map_or(ExitCode::FAILURE, |err| ExitCode::from(err.exit_code()))
 }
 
-fn main() -> Result<()> {
-    let args: Vec<String> = args().collect();
+/// Runs every statement in `input` against `env`, returning the last value
+/// produced along with its history number. Nothing is run unless the whole
+/// input parses; otherwise every lexical and syntax error found is returned.
+///
+/// A warning is printed when exact mode had to fall back to floats.
+fn evaluate<N: Numeric>(
+    input: &str,
+    env: &mut Environment<N>,
+) -> Result<Option<(usize, N::Value)>, Vec<Error>> {
+    let chars = input.chars().collect::<Vec<char>>();
+    let (tokens, mut errors) = tokenize(chars.iter().peekable());
+    let mut parser = Parser::new(tokens.iter().peekable()).recovering();
+    let statements = match parser.parse_program() {
+        Ok(statements) if errors.is_empty() => statements,
+        Ok(_) => return Err(errors),
+        Err(syntax_errors) => {
+            errors.extend(syntax_errors);
+            errors.sort_by_key(|err| err.span.map(|span| span.start));
+            return Err(errors);
+        }
+    };
+
+    if statements.is_empty() {
+        let err = KalcError::Parse("No expression provided".to_string());
+        return Err(vec![err.at(Span::new(0, chars.len()))]);
+    }
 
-    if args.len() > 1 {
-        if args[1] == "-h" || args[1] == "--help" {
-            print_help();
-            return Ok(());
-        } else if args[1] == "-v" || args[1] == "--version" {
-            println!("kalc {VERSION}");
-            return Ok(());
+    let mut last = None;
+    for statement in &statements {
+        match statement.execute(env) {
+            Ok(Some(value)) => last = Some((env.record(value.clone()), value)),
+            Ok(None) => {}
+            Err(err) => {
+                env.take_fallback();
+                return Err(vec![err]);
+            }
         }
     }
 
-    let expr = if args.len() <= 1 {
+    if let Some(span) = env.take_fallback() {
+        eprintln!("{}", KalcError::Inexact.at(span).render_warning(input));
+    }
+    Ok(last)
+}
+
+/// Reads and evaluates lines from stdin until `:quit` or end of input. The
+/// banner and prompt are only shown when stdin is a terminal so that piped
+/// input produces nothing but results.
+///
+/// Errors do not end the session; the exit status is that of the last line
+/// that failed, so a batch piped through kalc still reports failure.
+fn repl<N: Numeric>(mut env: Environment<N>, format: Format) -> Result<ExitCode, Error> {
+    let interactive = stdin().is_terminal();
+    if interactive {
         println!("kalc {VERSION}");
-        println!("Enter an expression (or type 'help' for instructions):");
+        println!("Enter an expression, or :help for instructions and :quit to leave");
+    }
 
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
+    let mut input = String::new();
+    let mut status = ExitCode::SUCCESS;
+    loop {
+        if interactive {
+            print!("> ");
+            stdout().flush()?;
+        }
 
-        if input.trim() == "help" {
-            print_help();
-            return Ok(());
-        } else if input.trim().is_empty() {
-            return Err(anyhow!("No expression provided"));
+        input.clear();
+        if stdin().read_line(&mut input)? == 0 {
+            if interactive {
+                println!();
+            }
+            return Ok(status);
         }
 
-        input
-    } else {
-        args[1..].join(" ")
-    };
+        match input.trim() {
+            "" => continue,
+            ":quit" | ":q" | ":exit" => return Ok(status),
+            ":help" | "help" => print_help(),
+            ":vars" => print_vars(&env, format),
+            ":clear" => env.clear(),
+            line if line.starts_with(':') => {
+                let err = KalcError::Usage(format!("Unknown command: {}", line));
+                status = report(&[err.into()], line);
+                if interactive {
+                    print_repl_commands();
+                }
+            }
+            line => match evaluate(line, &mut env) {
+                Ok(Some((n, result))) if interactive => {
+                    println!("${} = {}", n, env.backend.format(&result, format));
+                }
+                Ok(Some((_, result))) => println!("{}", env.backend.format(&result, format)),
+                Ok(None) => {}
+                Err(errors) => status = report(&errors, line),
+            },
+        }
+    }
+}
 
-    let chars = expr.chars().collect::<Vec<char>>();
-    let tokens = tokenize(chars.iter().peekable())?;
-    let mut ast = Parser::new(tokens.iter().peekable());
-    let ast = ast
-        .parse_program()?
-        .ok_or(anyhow!("unable to parse expression"))?;
-    let result = format_float(ast.eval());
+fn print_vars<N: Numeric>(env: &Environment<N>, format: Format) {
+    let mut vars = env.vars.iter().collect::<Vec<_>>();
+    vars.sort_by_key(|(name, _)| *name);
+    for (name, value) in vars {
+        println!("  {} = {}", name, env.backend.format(value, format));
+    }
 
-    println!("{result}");
+    let mut functions = env.functions.iter().collect::<Vec<_>>();
+    functions.sort_by_key(|(name, _)| *name);
+    for (name, function) in functions {
+        println!("  {}({})", name, function.params.join(", "));
+    }
 
-    Ok(())
+    if let Some(ans) = env.history.last() {
+        println!(
+            "  ans = {} (${})",
+            env.backend.format(ans, format),
+            env.history.len()
+        );
+    }
 }
@@ -0,0 +1,266 @@
+use std::cmp::Ordering;
+
+use num_complex::Complex64;
+
+use super::{Format, Numeric, float::format_f64, float_factorial};
+use crate::{error::KalcError, functions::Builtin};
+
+/// Complex numbers with float components, so that `sqrt(-1)` is `i`
+/// instead of a domain error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Complex;
+
+impl Numeric for Complex {
+    type Value = Complex64;
+
+    fn parse(&self, text: &str) -> Result<Complex64, KalcError> {
+        text.parse()
+            .map(real)
+            .map_err(|_| KalcError::Lex(format!("Invalid number: {}", text)))
+    }
+
+    fn imaginary(&self, text: &str) -> Result<Complex64, KalcError> {
+        self.parse(text).map(|z| z * Complex64::i())
+    }
+
+    fn float(&self, n: f64) -> Result<Complex64, KalcError> {
+        Ok(real(n))
+    }
+
+    /// Only real numbers have a float value.
+    fn to_f64(&self, value: &Complex64) -> f64 {
+        if value.im == 0.0 { value.re } else { f64::NAN }
+    }
+
+    fn format(&self, value: &Complex64, format: Format) -> String {
+        if format.polar {
+            let (r, theta) = value.to_polar();
+            return format!("{}∠{}°", format_f64(r), format_f64(theta.to_degrees()));
+        }
+
+        // Components lost in the rounding error of the other one, such as the
+        // imaginary part of e^(i x pi), are printed as zero. Infinite and NaN
+        // values have no rounding error to speak of.
+        let noise = if value.is_finite() {
+            value.norm() * f64::EPSILON * 4.0
+        } else {
+            0.0
+        };
+        let re = if value.re.abs() <= noise {
+            0.0
+        } else {
+            value.re
+        };
+        let im = if value.im.abs() <= noise {
+            0.0
+        } else {
+            value.im
+        };
+        let imaginary = |im: f64| match im {
+            1.0 => "i".to_string(),
+            _ => format!("{}i", format_f64(im)),
+        };
+        match (re, im) {
+            (re, 0.0) => format_f64(re),
+            (0.0, im) if im < 0.0 => format!("-{}", imaginary(-im)),
+            (0.0, im) => imaginary(im),
+            (re, im) if im < 0.0 => format!("{} - {}", format_f64(re), imaginary(-im)),
+            (re, im) => format!("{} + {}", format_f64(re), imaginary(im)),
+        }
+    }
+
+    fn add(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        Ok(a + b)
+    }
+
+    fn sub(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        Ok(a - b)
+    }
+
+    fn mul(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        Ok(a * b)
+    }
+
+    fn div(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        Ok(a / b)
+    }
+
+    fn floor_div(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        let (a, b) = reals("//", a, b)?;
+        Ok(real((a / b).floor()))
+    }
+
+    fn rem(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        let (a, b) = reals("%", a, b)?;
+        Ok(real(a % b))
+    }
+
+    fn modulo(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        let (a, b) = reals("mod", a, b)?;
+        Ok(real(a.rem_euclid(b)))
+    }
+
+    /// Integer powers are taken by repeated multiplication so that `i^2` is
+    /// exactly -1, and real powers of non-negative numbers stay real.
+    fn pow(&self, a: &Complex64, b: &Complex64) -> Result<Complex64, KalcError> {
+        Ok(match (a.im, b.im) {
+            (_, 0.0) if b.re.fract() == 0.0 && b.re.abs() <= i32::MAX as f64 => a.powi(b.re as i32),
+            (0.0, 0.0) if a.re >= 0.0 => real(a.re.powf(b.re)),
+            _ => a.powc(*b),
+        })
+    }
+
+    /// Negating a real number keeps its imaginary part +0 rather than -0,
+    /// which would put `-1` below the branch cut of sqrt and ln.
+    fn neg(&self, a: &Complex64) -> Result<Complex64, KalcError> {
+        Ok(Complex64::new(-a.re, -a.im + 0.0))
+    }
+
+    fn factorial(&self, n: &Complex64) -> Result<Complex64, KalcError> {
+        if n.im != 0.0 {
+            return Err(KalcError::Domain(
+                "Factorial expects a non-negative integer".to_string(),
+            ));
+        }
+        float_factorial(n.re).map(real)
+    }
+
+    /// Complex numbers can only be told equal or unequal; real ones are
+    /// ordered as usual.
+    fn compare(&self, a: &Complex64, b: &Complex64) -> Option<Ordering> {
+        if a.im == 0.0 && b.im == 0.0 {
+            a.re.partial_cmp(&b.re)
+        } else {
+            (a == b).then_some(Ordering::Equal)
+        }
+    }
+
+    fn truth(&self, holds: bool) -> Complex64 {
+        real(holds as u8 as f64)
+    }
+
+    fn is_zero(&self, value: &Complex64) -> bool {
+        *value == Complex64::ZERO
+    }
+
+    fn is_negative(&self, value: &Complex64) -> bool {
+        value.im == 0.0 && value.re < 0.0
+    }
+
+    fn is_finite(&self, value: &Complex64) -> bool {
+        value.is_finite()
+    }
+
+    fn is_nan(&self, value: &Complex64) -> bool {
+        value.is_nan()
+    }
+
+    /// Real arguments inside a function's real domain get the real version,
+    /// which is more accurate. Outside of it, and for complex arguments, the
+    /// principal value of the complex version is used.
+    fn call(
+        &self,
+        builtin: &Builtin,
+        args: &[Complex64],
+        ieee: bool,
+    ) -> Result<Complex64, KalcError> {
+        let reals = args
+            .iter()
+            .map(|z| (z.im == 0.0).then_some(z.re))
+            .collect::<Option<Vec<_>>>();
+        let Some(reals) = reals else {
+            return complex_builtin(builtin.name, args).ok_or_else(|| {
+                KalcError::Domain(format!(
+                    "Function '{}' expects real arguments",
+                    builtin.name
+                ))
+            });
+        };
+
+        match builtin.check_domain(&reals, false) {
+            Ok(()) => builtin.call(&reals).map(real),
+            Err(err) => match complex_builtin(builtin.name, args) {
+                Some(result) if result.is_finite() => Ok(result),
+                _ if ieee => builtin.call(&reals).map(real),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+fn real(n: f64) -> Complex64 {
+    Complex64::new(n, 0.0)
+}
+
+/// The real values of the operands of `op`, which is only defined for reals.
+fn reals(op: &str, a: &Complex64, b: &Complex64) -> Result<(f64, f64), KalcError> {
+    if a.im != 0.0 || b.im != 0.0 {
+        return Err(KalcError::Domain(format!(
+            "Operator '{}' expects real operands",
+            op
+        )));
+    }
+    Ok((a.re, b.re))
+}
+
+/// Built-ins with a complex counterpart.
+fn complex_builtin(name: &str, args: &[Complex64]) -> Option<Complex64> {
+    let z = args[0];
+    Some(match name {
+        "sqrt" => z.sqrt(),
+        "cbrt" => z.cbrt(),
+        "exp" => z.exp(),
+        "ln" => z.ln(),
+        "log10" => z.log10(),
+        "log2" => z.log2(),
+        "log" => match args.get(1) {
+            Some(base) => z.ln() / base.ln(),
+            None => z.log10(),
+        },
+        "sin" => z.sin(),
+        "cos" => z.cos(),
+        "tan" => z.tan(),
+        "asin" => z.asin(),
+        "acos" => z.acos(),
+        "atan" => z.atan(),
+        "sinh" => z.sinh(),
+        "cosh" => z.cosh(),
+        "tanh" => z.tanh(),
+        "asinh" => z.asinh(),
+        "acosh" => z.acosh(),
+        "atanh" => z.atanh(),
+        "abs" => real(z.norm()),
+        "re" => real(z.re),
+        "im" => real(z.im),
+        "arg" => real(z.arg()),
+        "conj" => z.conj(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(re: f64, im: f64) -> String {
+        Complex.format(&Complex64::new(re, im), Format::default())
+    }
+
+    #[test]
+    fn rounding_noise_is_zero() {
+        assert_eq!(format(-1.0, 1.2246467991473532e-16), "-1");
+        assert_eq!(format(6.123233995736766e-17, 1.0), "i");
+        assert_eq!(format(3.0, -4.0), "3 - 4i");
+    }
+
+    #[test]
+    fn infinite_and_nan_parts() {
+        assert_eq!(format(f64::INFINITY, 0.0), "inf");
+        assert_eq!(format(f64::NEG_INFINITY, 0.0), "-inf");
+        assert_eq!(format(0.0, f64::INFINITY), "infi");
+        assert_eq!(format(1.0, f64::INFINITY), "1 + infi");
+        assert_eq!(format(f64::INFINITY, 1.0), "inf + i");
+        assert_eq!(format(f64::NAN, 0.0), "NaN");
+        assert_eq!(format(1.0, f64::NAN), "1 + NaNi");
+    }
+}
@@ -0,0 +1,323 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use bigdecimal::{BigDecimal, Context, RoundingMode};
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use super::{Format, MAX_FACTORIAL, Numeric, exact_factorial, nonzero, not_natural, to_f64};
+use crate::error::KalcError;
+
+/// Significant digits kept by `--decimal` when no `--precision` is given,
+/// matching IEEE-754 decimal128.
+pub const DEFAULT_PRECISION: u64 = 34;
+
+/// Largest `--precision` accepted, in significant digits.
+pub const MAX_PRECISION: u64 = 100_000;
+
+/// Largest magnitude, in decimal digits, a literal or a decimal power may
+/// reach before it is reported as an overflow instead of being computed.
+const MAX_DECIMAL_DIGITS: u64 = 1_000_000;
+
+/// Decimals rounded to the precision and rounding mode of the context after
+/// every operation, so that `0.1 + 0.2` is exactly `0.3`.
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    context: Context,
+}
+
+impl Decimal {
+    /// Decimals keeping `precision` significant digits.
+    pub fn new(precision: u64, rounding: RoundingMode) -> Option<Self> {
+        let context = Context::default()
+            .with_prec(precision)?
+            .with_rounding_mode(rounding);
+        Some(Self { context })
+    }
+
+    fn round(&self, d: BigDecimal) -> BigDecimal {
+        self.context.round_decimal(d)
+    }
+}
+
+impl Numeric for Decimal {
+    type Value = BigDecimal;
+
+    fn parse(&self, text: &str) -> Result<BigDecimal, KalcError> {
+        let d = BigDecimal::from_str(text)
+            .map_err(|_| KalcError::Lex(format!("Invalid number: {}", text)))?;
+        // Keeps exponents such as 1e999999999 from being expanded later.
+        if d.order_of_magnitude().unsigned_abs() > MAX_DECIMAL_DIGITS {
+            return Err(KalcError::Overflow(None));
+        }
+        Ok(self.round(d))
+    }
+
+    /// Goes through the shortest representation of `n` that reads back as the
+    /// same float, so that 0.1 stays 0.1 rather than becoming its binary
+    /// approximation.
+    fn float(&self, n: f64) -> Result<BigDecimal, KalcError> {
+        if n.is_nan() {
+            return Err(KalcError::Domain("Result is not a real number".to_string()));
+        }
+        let d = BigDecimal::from_str(&n.to_string()).map_err(|_| KalcError::Overflow(None))?;
+        Ok(self.round(d))
+    }
+
+    fn to_f64(&self, value: &BigDecimal) -> f64 {
+        to_f64(value)
+    }
+
+    fn integer(&self, value: &BigDecimal) -> Option<BigInt> {
+        value
+            .is_integer()
+            .then(|| value.with_scale(0).into_bigint_and_exponent().0)
+    }
+
+    fn format(&self, value: &BigDecimal, _format: Format) -> String {
+        // Rounding pads decimals with trailing zeros up to the precision.
+        value.normalized().to_plain_string()
+    }
+
+    fn add(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        Ok(self.round(a + b))
+    }
+
+    fn sub(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        Ok(self.round(a - b))
+    }
+
+    fn mul(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        Ok(self.round(a * b))
+    }
+
+    /// `a / b` rounded once to the precision of the context.
+    fn div(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        nonzero(b)?;
+        let (a_digits, a_scale) = a.as_bigint_and_exponent();
+        let (b_digits, b_scale) = b.as_bigint_and_exponent();
+
+        // Widen the numerator so the integer quotient carries at least two
+        // digits beyond the precision. A non-zero remainder is kept as one
+        // more trailing digit, so that rounding still sees the quotient is
+        // inexact.
+        let wanted = self.context.precision().get() as i64 + 2;
+        let shift = (wanted + b.digits() as i64 - a.digits() as i64).max(0);
+        let numerator = a_digits * BigInt::from(10).pow(shift as u32);
+        let quotient = &numerator / &b_digits;
+        let remainder = numerator - &quotient * &b_digits;
+
+        let mut scale = a_scale - b_scale + shift;
+        let quotient = if remainder.is_zero() {
+            quotient
+        } else {
+            scale += 1;
+            let sticky = if a.is_negative() == b.is_negative() {
+                1
+            } else {
+                -1
+            };
+            quotient * 10 + sticky
+        };
+        Ok(self.round(BigDecimal::new(quotient, scale)))
+    }
+
+    /// The largest integer not greater than `a / b`, computed exactly.
+    fn floor_div(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        nonzero(b)?;
+        let scale = a.fractional_digit_count().max(b.fractional_digit_count());
+        let (a, _) = a.with_scale(scale).into_bigint_and_exponent();
+        let (b, _) = b.with_scale(scale).into_bigint_and_exponent();
+        let quotient = &a / &b;
+        let truncated = (&a % &b).is_zero() || a.is_negative() == b.is_negative();
+        Ok(self.round(BigDecimal::from(if truncated {
+            quotient
+        } else {
+            quotient - 1
+        })))
+    }
+
+    fn rem(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        nonzero(b)?;
+        Ok(self.round(a % b))
+    }
+
+    fn modulo(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        nonzero(b)?;
+        let r = a % b;
+        Ok(self.round(if r.is_negative() { r + b.abs() } else { r }))
+    }
+
+    /// Integer powers are computed in decimal; anything else goes through
+    /// floats.
+    fn pow(&self, a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        if a.is_zero() && b.is_negative() {
+            return Err(KalcError::DivisionByZero);
+        }
+        if let Some(exponent) = b.is_integer().then(|| b.to_i64()).flatten() {
+            let magnitude = a.order_of_magnitude().unsigned_abs() + 1;
+            if exponent
+                .unsigned_abs()
+                .checked_mul(magnitude)
+                .is_none_or(|digits| digits > MAX_DECIMAL_DIGITS)
+            {
+                return Err(KalcError::Overflow(None));
+            }
+            return Ok(a.powi_with_context(exponent, &self.context));
+        }
+
+        self.float(to_f64(a).powf(to_f64(b)))
+    }
+
+    fn neg(&self, a: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        Ok(-a)
+    }
+
+    fn factorial(&self, n: &BigDecimal) -> Result<BigDecimal, KalcError> {
+        if !n.is_integer() {
+            return Err(not_natural());
+        }
+        // Keeps huge exponents such as 1E+999999999 from being expanded.
+        if to_f64(n) > MAX_FACTORIAL as f64 {
+            return Err(KalcError::Overflow(None));
+        }
+        let (n, _) = n.with_scale(0).into_bigint_and_exponent();
+        exact_factorial(&n).map(|product| self.round(product.into()))
+    }
+
+    fn compare(&self, a: &BigDecimal, b: &BigDecimal) -> Option<Ordering> {
+        Some(a.cmp(b))
+    }
+
+    fn truth(&self, holds: bool) -> BigDecimal {
+        BigDecimal::from(holds as u8)
+    }
+
+    fn is_zero(&self, value: &BigDecimal) -> bool {
+        value.is_zero()
+    }
+
+    fn is_negative(&self, value: &BigDecimal) -> bool {
+        value.is_negative()
+    }
+
+    fn builtin(&self, name: &str, args: &[BigDecimal]) -> Option<BigDecimal> {
+        let context = &self.context;
+        let x = &args[0];
+        let integer = |mode| x.with_scale_round(0, mode);
+        Some(match name {
+            "sqrt" => x.sqrt_with_context(context)?,
+            "cbrt" => x.cbrt_with_context(context),
+            // Beyond this the result has over a million digits; let the float
+            // version report the overflow.
+            "exp" if x.abs() < 1_000_000 => x.exp_with_context(context),
+            "abs" => x.abs(),
+            "floor" => integer(RoundingMode::Floor),
+            "ceil" => integer(RoundingMode::Ceiling),
+            "round" => integer(RoundingMode::HalfUp),
+            "trunc" => integer(RoundingMode::Down),
+            "sign" => x.signum(),
+            "min" => args.iter().min()?.clone(),
+            "max" => args.iter().max()?.clone(),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(text: &str) -> BigDecimal {
+        BigDecimal::from_str(text).unwrap()
+    }
+
+    fn divide(precision: u64, rounding: RoundingMode, a: &str, b: &str) -> String {
+        let decimal = Decimal::new(precision, rounding).unwrap();
+        let quotient = decimal.div(&d(a), &d(b)).unwrap();
+        decimal.format(&quotient, Format::default())
+    }
+
+    // 1/8 is a tie, 2/3 is inexact, 1/7.99999 = 0.12500015... is just above
+    // a tie, which only the sticky digit shows, and 1/4.00001 = 0.2499993...
+    // is just below a result that is exact at two digits.
+    #[test]
+    fn div_rounds_to_precision() {
+        let divisions = [
+            ("1", "8"),
+            ("-1", "8"),
+            ("2", "3"),
+            ("-2", "3"),
+            ("1", "7.99999"),
+            ("1", "4.00001"),
+            ("-1", "4.00001"),
+        ];
+        let quotients = [
+            (
+                RoundingMode::HalfEven,
+                ["0.12", "-0.12", "0.67", "-0.67", "0.13", "0.25", "-0.25"],
+            ),
+            (
+                RoundingMode::HalfUp,
+                ["0.13", "-0.13", "0.67", "-0.67", "0.13", "0.25", "-0.25"],
+            ),
+            (
+                RoundingMode::HalfDown,
+                ["0.12", "-0.12", "0.67", "-0.67", "0.13", "0.25", "-0.25"],
+            ),
+            (
+                RoundingMode::Up,
+                ["0.13", "-0.13", "0.67", "-0.67", "0.13", "0.25", "-0.25"],
+            ),
+            (
+                RoundingMode::Down,
+                ["0.12", "-0.12", "0.66", "-0.66", "0.12", "0.24", "-0.24"],
+            ),
+            (
+                RoundingMode::Ceiling,
+                ["0.13", "-0.12", "0.67", "-0.66", "0.13", "0.25", "-0.24"],
+            ),
+            (
+                RoundingMode::Floor,
+                ["0.12", "-0.13", "0.66", "-0.67", "0.12", "0.24", "-0.25"],
+            ),
+        ];
+        for (rounding, expected) in quotients {
+            for ((a, b), expected) in divisions.iter().zip(expected) {
+                assert_eq!(
+                    divide(2, rounding, a, b),
+                    expected,
+                    "{} / {} rounding {:?}",
+                    a,
+                    b,
+                    rounding
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn floor_div_rounds_down() {
+        let decimal = Decimal::new(34, RoundingMode::HalfEven).unwrap();
+        for (a, b, expected) in [
+            ("7", "2", "3"),
+            ("-7", "2", "-4"),
+            ("7", "-2", "-4"),
+            ("-7", "-2", "3"),
+            ("-6", "3", "-2"),
+            ("-7.5", "2", "-4"),
+            ("7.5", "2.5", "3"),
+        ] {
+            let quotient = decimal.floor_div(&d(a), &d(b)).unwrap();
+            assert_eq!(quotient, d(expected), "{} // {}", a, b);
+        }
+    }
+
+    #[test]
+    fn remainder_signs() {
+        let decimal = Decimal::new(34, RoundingMode::HalfEven).unwrap();
+        assert_eq!(decimal.rem(&d("-7.5"), &d("2")).unwrap(), d("-1.5"));
+        assert_eq!(decimal.modulo(&d("-7.5"), &d("2")).unwrap(), d("0.5"));
+        assert_eq!(decimal.modulo(&d("7.5"), &d("-2")).unwrap(), d("1.5"));
+        assert!(decimal.div(&d("1"), &d("0")).is_err());
+    }
+}
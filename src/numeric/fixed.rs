@@ -0,0 +1,357 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_integer::Integer as _;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use super::{
+    Format, Numeric, exact_factorial, float::format_f64, format_radix, integer::integer_builtin,
+    nonzero,
+};
+use crate::{error::KalcError, parser::Op};
+
+/// Widths `--int` accepts, in bits.
+pub const WIDTHS: [u32; 5] = [8, 16, 32, 64, 128];
+
+/// Integers of a fixed width, as in C or Rust: `--int u8` computes with
+/// integers from 0 to 255. Results out of range either wrap around or are
+/// errors.
+#[derive(Debug, Clone, Copy)]
+pub struct Fixed {
+    bits: u32,
+    signed: bool,
+    wrap: bool,
+}
+
+impl Fixed {
+    pub fn new(bits: u32, signed: bool, wrap: bool) -> Self {
+        Self { bits, signed, wrap }
+    }
+
+    /// `2^bits`, the number of values of the type.
+    fn modulus(&self) -> BigInt {
+        BigInt::one() << self.bits
+    }
+
+    fn min(&self) -> BigInt {
+        if self.signed {
+            -(BigInt::one() << (self.bits - 1))
+        } else {
+            BigInt::zero()
+        }
+    }
+
+    fn max(&self) -> BigInt {
+        self.min() + self.modulus() - 1
+    }
+
+    /// The value whose two's complement representation is `pattern`.
+    fn value_of(&self, pattern: BigInt) -> BigInt {
+        let n = pattern.mod_floor(&self.modulus());
+        if n > self.max() {
+            n - self.modulus()
+        } else {
+            n
+        }
+    }
+
+    /// The two's complement representation of `n`.
+    fn bits_of(&self, n: &BigInt) -> BigInt {
+        n.mod_floor(&self.modulus())
+    }
+
+    /// `n` as a value of the type, wrapped around or rejected when it is
+    /// out of range.
+    fn fit(&self, n: BigInt) -> Result<BigInt, KalcError> {
+        if n >= self.min() && n <= self.max() {
+            Ok(n)
+        } else if self.wrap {
+            Ok(self.value_of(n))
+        } else {
+            Err(self.out_of_range())
+        }
+    }
+
+    fn out_of_range(&self) -> KalcError {
+        KalcError::Overflow(Some(format!(
+            "Result does not fit in {}; use --wrap to wrap around",
+            self
+        )))
+    }
+
+    /// The amount a value is shifted by, from 0 to one less than the width.
+    /// Wrapping integers take it modulo the width, as Rust's
+    /// `wrapping_shl` does.
+    fn shift_amount(&self, b: &BigInt) -> Result<usize, KalcError> {
+        let bits = BigInt::from(self.bits);
+        if self.wrap {
+            return Ok(b.mod_floor(&bits).to_usize().unwrap_or(0));
+        }
+        b.to_u32()
+            .filter(|&amount| amount < self.bits)
+            .map(|amount| amount as usize)
+            .ok_or_else(|| {
+                KalcError::Domain(format!(
+                    "Shift amount {} is out of range for {}: expected 0 to {}",
+                    b,
+                    self,
+                    self.bits - 1
+                ))
+            })
+    }
+}
+
+/// Written as the Rust type, `i32` or `u8`.
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.signed { 'i' } else { 'u' };
+        write!(f, "{}{}", sign, self.bits)
+    }
+}
+
+impl Numeric for Fixed {
+    type Value = BigInt;
+
+    /// Literals such as `1e3` are accepted as long as they are integers.
+    fn parse(&self, text: &str) -> Result<BigInt, KalcError> {
+        let n = match BigInt::from_str(text) {
+            Ok(n) => n,
+            Err(_) => {
+                let d = BigDecimal::from_str(text)
+                    .map_err(|_| KalcError::Lex(format!("Invalid number: {}", text)))?;
+                if !d.is_integer() {
+                    return Err(KalcError::Domain(format!(
+                        "{} is not an integer, which {} needs",
+                        text, self
+                    )));
+                }
+                // Keeps huge exponents such as 1e999999999 from being expanded.
+                if d.fractional_digit_count() < -i64::from(self.bits) {
+                    return Err(KalcError::Overflow(None));
+                }
+                d.with_scale(0).into_bigint_and_exponent().0
+            }
+        };
+        self.fit(n)
+    }
+
+    /// Results of functions and constants are only accepted when they are
+    /// integers.
+    fn float(&self, n: f64) -> Result<BigInt, KalcError> {
+        match BigInt::from_f64(n).filter(|_| n.fract() == 0.0) {
+            Some(i) => self.fit(i),
+            None => Err(KalcError::Domain(format!(
+                "{} is not an integer, which {} needs",
+                format_f64(n),
+                self
+            ))),
+        }
+    }
+
+    fn to_f64(&self, value: &BigInt) -> f64 {
+        value.to_f64().unwrap_or(f64::NAN)
+    }
+
+    fn integer(&self, value: &BigInt) -> Option<BigInt> {
+        Some(value.clone())
+    }
+
+    fn format(&self, value: &BigInt, _format: Format) -> String {
+        value.to_string()
+    }
+
+    /// Negative values are written in two's complement outside base 10:
+    /// `-1` in `i8` is `0xff`.
+    fn format_in(&self, value: &BigInt, radix: u32) -> Option<String> {
+        if radix == 10 {
+            return Some(value.to_string());
+        }
+        Some(format_radix(&self.bits_of(value), radix))
+    }
+
+    fn add(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(a + b)
+    }
+
+    fn sub(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(a - b)
+    }
+
+    fn mul(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(a * b)
+    }
+
+    /// Integer division truncates towards zero, as in C.
+    fn div(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(a / nonzero(b)?)
+    }
+
+    fn floor_div(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(a.div_floor(nonzero(b)?))
+    }
+
+    fn rem(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        Ok(a % nonzero(b)?)
+    }
+
+    fn modulo(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        Ok(a.mod_floor(&nonzero(b)?.abs()))
+    }
+
+    fn pow(&self, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        if b.is_negative() {
+            return Err(KalcError::Domain(format!(
+                "Negative powers are not integers, which {} needs",
+                self
+            )));
+        }
+        if self.wrap {
+            return Ok(self.value_of(a.modpow(b, &self.modulus())));
+        }
+        // Any base other than 0, 1 and -1 is out of range long before the
+        // exponent gets past the width.
+        match b.to_u32() {
+            Some(exponent) if a.magnitude().bits() <= 1 || exponent <= self.bits => {
+                self.fit(a.pow(exponent))
+            }
+            _ => Err(self.out_of_range()),
+        }
+    }
+
+    fn neg(&self, a: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(-a)
+    }
+
+    fn factorial(&self, n: &BigInt) -> Result<BigInt, KalcError> {
+        self.fit(exact_factorial(n)?)
+    }
+
+    /// Bitwise operators work on the two's complement representation.
+    fn bitwise(&self, op: &Op, a: &BigInt, b: &BigInt) -> Result<BigInt, KalcError> {
+        match op {
+            Op::BitAnd => Ok(a & b),
+            Op::BitOr => Ok(a | b),
+            Op::BitXor => Ok(a ^ b),
+            Op::Shl => self.fit(a << self.shift_amount(b)?),
+            Op::Shr => Ok(a >> self.shift_amount(b)?),
+            Op::UShr => Ok(self.value_of(self.bits_of(a) >> self.shift_amount(b)?)),
+            _ => unreachable!("not a bitwise operator"),
+        }
+    }
+
+    fn bit_not(&self, a: &BigInt) -> Result<BigInt, KalcError> {
+        Ok(self.value_of(!a))
+    }
+
+    fn compare(&self, a: &BigInt, b: &BigInt) -> Option<Ordering> {
+        Some(a.cmp(b))
+    }
+
+    fn truth(&self, holds: bool) -> BigInt {
+        BigInt::from(holds as u8)
+    }
+
+    fn is_zero(&self, value: &BigInt) -> bool {
+        value.is_zero()
+    }
+
+    fn is_negative(&self, value: &BigInt) -> bool {
+        value.is_negative()
+    }
+
+    /// Results out of range, such as `abs` of the minimum, go on to floats
+    /// and are rejected there.
+    fn builtin(&self, name: &str, args: &[BigInt]) -> Option<BigInt> {
+        let args = args.iter().collect::<Vec<_>>();
+        integer_builtin(name, &args).and_then(|n| self.fit(n).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I8: Fixed = Fixed {
+        bits: 8,
+        signed: true,
+        wrap: false,
+    };
+    const I8_WRAP: Fixed = Fixed { wrap: true, ..I8 };
+    const U8: Fixed = Fixed {
+        signed: false,
+        ..I8
+    };
+    const U8_WRAP: Fixed = Fixed { wrap: true, ..U8 };
+
+    fn int(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    fn overflows(result: Result<BigInt, KalcError>) -> bool {
+        matches!(result, Err(KalcError::Overflow(Some(_))))
+    }
+
+    #[test]
+    fn traps_out_of_range() {
+        assert!(overflows(I8.add(&int(127), &int(1))));
+        assert!(overflows(I8.neg(&int(-128))));
+        assert!(overflows(I8.parse("128")));
+        assert!(overflows(U8.sub(&int(0), &int(1))));
+        assert!(overflows(U8.pow(&int(2), &int(8))));
+        assert_eq!(U8.pow(&int(2), &int(7)).unwrap(), int(128));
+        assert_eq!(I8.parse("-128").unwrap(), int(-128));
+    }
+
+    #[test]
+    fn wraps_around() {
+        assert_eq!(I8_WRAP.add(&int(127), &int(1)).unwrap(), int(-128));
+        assert_eq!(I8_WRAP.mul(&int(100), &int(3)).unwrap(), int(44));
+        assert_eq!(I8_WRAP.neg(&int(-128)).unwrap(), int(-128));
+        assert_eq!(U8_WRAP.sub(&int(0), &int(1)).unwrap(), int(255));
+        assert_eq!(U8_WRAP.pow(&int(2), &int(8)).unwrap(), int(0));
+        assert_eq!(U8_WRAP.pow(&int(3), &int(5)).unwrap(), int(243));
+    }
+
+    #[test]
+    fn shifts() {
+        let shift = |fixed: Fixed, op: Op, a: i64, b: i64| fixed.bitwise(&op, &int(a), &int(b));
+        assert_eq!(shift(I8, Op::Shl, 1, 6).unwrap(), int(64));
+        assert!(overflows(shift(I8, Op::Shl, 1, 7)));
+        assert_eq!(shift(I8_WRAP, Op::Shl, 1, 7).unwrap(), int(-128));
+        assert_eq!(shift(I8, Op::Shr, -128, 1).unwrap(), int(-64));
+        assert_eq!(shift(I8, Op::UShr, -128, 1).unwrap(), int(64));
+        assert_eq!(shift(I8, Op::UShr, -1, 4).unwrap(), int(15));
+        assert!(matches!(
+            shift(I8, Op::Shl, 1, 8),
+            Err(KalcError::Domain(_))
+        ));
+        assert!(matches!(
+            shift(I8, Op::Shr, 1, -1),
+            Err(KalcError::Domain(_))
+        ));
+        assert_eq!(shift(I8_WRAP, Op::Shl, 1, 9).unwrap(), int(2));
+    }
+
+    #[test]
+    fn bitwise_on_twos_complement() {
+        assert_eq!(
+            I8.bitwise(&Op::BitAnd, &int(-1), &int(0x0f)).unwrap(),
+            int(15)
+        );
+        assert_eq!(I8.bitwise(&Op::BitXor, &int(-1), &int(1)).unwrap(), int(-2));
+        assert_eq!(I8.bit_not(&int(0)).unwrap(), int(-1));
+        assert_eq!(U8.bit_not(&int(0)).unwrap(), int(255));
+    }
+
+    #[test]
+    fn formats_in_twos_complement() {
+        assert_eq!(I8.format_in(&int(-1), 16).unwrap(), "0xff");
+        assert_eq!(I8.format_in(&int(-128), 2).unwrap(), "0b10000000");
+        assert_eq!(I8.format_in(&int(-1), 10).unwrap(), "-1");
+        assert_eq!(U8.format_in(&int(255), 16).unwrap(), "0xff");
+        let i16 = Fixed::new(16, true, false);
+        assert_eq!(i16.format_in(&int(-2), 16).unwrap(), "0xfffe");
+        assert_eq!(i16.format_in(&int(-2), 8).unwrap(), "0o177776");
+    }
+}
@@ -0,0 +1,140 @@
+use std::cmp::Ordering;
+
+use super::{Format, Numeric, float_factorial};
+use crate::error::KalcError;
+
+/// Magnitude from which consecutive integers are no longer all representable
+/// as floats, so that printing every digit would be misleading.
+const MAX_EXACT_FLOAT: f64 = 9_007_199_254_740_992.0;
+
+/// IEEE-754 double precision floats, integers included.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Float;
+
+impl Numeric for Float {
+    type Value = f64;
+
+    fn parse(&self, text: &str) -> Result<f64, KalcError> {
+        text.parse()
+            .map_err(|_| KalcError::Lex(format!("Invalid number: {}", text)))
+    }
+
+    fn float(&self, n: f64) -> Result<f64, KalcError> {
+        Ok(n)
+    }
+
+    fn to_f64(&self, value: &f64) -> f64 {
+        *value
+    }
+
+    fn format(&self, value: &f64, _format: Format) -> String {
+        format_f64(*value)
+    }
+
+    fn add(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a + b)
+    }
+
+    fn sub(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a - b)
+    }
+
+    fn mul(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a * b)
+    }
+
+    fn div(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a / b)
+    }
+
+    fn floor_div(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(floor_div(*a, *b))
+    }
+
+    fn rem(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a % b)
+    }
+
+    fn modulo(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a.rem_euclid(*b))
+    }
+
+    fn pow(&self, a: &f64, b: &f64) -> Result<f64, KalcError> {
+        Ok(a.powf(*b))
+    }
+
+    fn neg(&self, a: &f64) -> Result<f64, KalcError> {
+        Ok(-a)
+    }
+
+    fn factorial(&self, n: &f64) -> Result<f64, KalcError> {
+        float_factorial(*n)
+    }
+
+    fn compare(&self, a: &f64, b: &f64) -> Option<Ordering> {
+        a.partial_cmp(b)
+    }
+
+    fn truth(&self, holds: bool) -> f64 {
+        holds as u8 as f64
+    }
+
+    fn is_zero(&self, value: &f64) -> bool {
+        *value == 0.0
+    }
+
+    fn is_negative(&self, value: &f64) -> bool {
+        *value < 0.0
+    }
+
+    fn is_finite(&self, value: &f64) -> bool {
+        value.is_finite()
+    }
+}
+
+pub(super) fn floor_div(a: f64, b: f64) -> f64 {
+    (a / b).floor()
+}
+
+/// Integral floats print without a fraction, unless they are too large for
+/// all of their digits to be meaningful.
+pub(super) fn format_f64(n: f64) -> String {
+    if n.abs() >= MAX_EXACT_FLOAT {
+        format!("{:e}", n)
+    } else if n.fract() == 0.0 {
+        format!("{:.0}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `%` takes the sign of the dividend, `mod` is never negative and `//`
+    /// rounds down, for every combination of signs.
+    #[test]
+    fn division_sign_rules() {
+        let cases = [
+            (7.0, 3.0, 1.0, 1.0, 2.0),
+            (-7.0, 3.0, -1.0, 2.0, -3.0),
+            (7.0, -3.0, 1.0, 1.0, -3.0),
+            (-7.0, -3.0, -1.0, 2.0, 2.0),
+            (-7.0, 2.0, -1.0, 1.0, -4.0),
+            (6.0, -3.0, 0.0, 0.0, -2.0),
+        ];
+        for (a, b, rem, modulo, floor) in cases {
+            assert_eq!(Float.rem(&a, &b).unwrap(), rem, "{} % {}", a, b);
+            assert_eq!(Float.modulo(&a, &b).unwrap(), modulo, "{} mod {}", a, b);
+            assert_eq!(Float.floor_div(&a, &b).unwrap(), floor, "{} // {}", a, b);
+        }
+    }
+
+    #[test]
+    fn fractional_operands() {
+        assert_eq!(Float.rem(&-5.5, &2.0).unwrap(), -1.5);
+        assert_eq!(Float.modulo(&-5.5, &2.0).unwrap(), 0.5);
+        assert_eq!(Float.floor_div(&-5.5, &2.0).unwrap(), -3.0);
+    }
+}
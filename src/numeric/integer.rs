@@ -0,0 +1,262 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_integer::Integer as _;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+
+use super::{
+    Approx, Format, MAX_EXACT_BITS, Numeric, exact_factorial,
+    float::{floor_div, format_f64},
+    float_factorial,
+};
+use crate::error::KalcError;
+
+/// Integers of any size, exact for as long as the operations on them stay
+/// integral. Fractions and anything computed from them are floats.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Integer;
+
+type Value = Approx<BigInt>;
+
+impl Numeric for Integer {
+    type Value = Value;
+
+    fn parse(&self, text: &str) -> Result<Value, KalcError> {
+        let invalid = || KalcError::Lex(format!("Invalid number: {}", text));
+        if text.contains('.') {
+            return text.parse().map(Approx::Float).map_err(|_| invalid());
+        }
+        // Whole numbers with a positive exponent, such as 2e9, stay exact.
+        let Some((digits, exponent)) = text.split_once(['e', 'E']) else {
+            return text.parse().map(Approx::Exact).map_err(|_| invalid());
+        };
+        match exponent.parse::<u32>() {
+            Ok(exponent) if u64::from(exponent) * 10 / 3 > MAX_EXACT_BITS => {
+                Err(KalcError::Overflow(None))
+            }
+            Ok(exponent) => {
+                let digits: BigInt = digits.parse().map_err(|_| invalid())?;
+                Ok(Approx::Exact(digits * BigInt::from(10).pow(exponent)))
+            }
+            Err(_) if !exponent.starts_with('-') => Err(KalcError::Overflow(None)),
+            Err(_) => text.parse().map(Approx::Float).map_err(|_| invalid()),
+        }
+    }
+
+    fn float(&self, n: f64) -> Result<Value, KalcError> {
+        Ok(Approx::Float(n))
+    }
+
+    fn to_f64(&self, value: &Value) -> f64 {
+        value.to_f64()
+    }
+
+    fn integer(&self, value: &Value) -> Option<BigInt> {
+        match value {
+            Approx::Exact(i) => Some(i.clone()),
+            Approx::Float(n) => (n.fract() == 0.0).then(|| BigInt::from_f64(*n)).flatten(),
+        }
+    }
+
+    fn format(&self, value: &Value, _format: Format) -> String {
+        match value {
+            Approx::Exact(i) => i.to_string(),
+            Approx::Float(n) => format_f64(*n),
+        }
+    }
+
+    fn add(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, |a, b| Ok(Approx::Exact(a + b)), |a, b| a + b)
+    }
+
+    fn sub(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, |a, b| Ok(Approx::Exact(a - b)), |a, b| a - b)
+    }
+
+    fn mul(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, |a, b| Ok(Approx::Exact(a * b)), |a, b| a * b)
+    }
+
+    fn div(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |x, y| {
+                Ok(if !y.is_zero() && (x % y).is_zero() {
+                    Approx::Exact(x / y)
+                } else {
+                    Approx::Float(a.to_f64() / b.to_f64())
+                })
+            },
+            |a, b| a / b,
+        )
+    }
+
+    fn floor_div(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        // Division by zero only gets this far with --ieee.
+        Approx::combine(
+            a,
+            b,
+            |x, y| Ok(integral(y, || x.div_floor(y), a, b, floor_div)),
+            floor_div,
+        )
+    }
+
+    fn rem(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |x, y| Ok(integral(y, || x % y, a, b, |a, b| a % b)),
+            |a, b| a % b,
+        )
+    }
+
+    fn modulo(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |x, y| Ok(integral(y, || x.mod_floor(&y.abs()), a, b, f64::rem_euclid)),
+            f64::rem_euclid,
+        )
+    }
+
+    fn pow(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |x, y| {
+                // Negative or huge exponents; the float either has a
+                // fraction or overflows.
+                Ok(match y.to_u32() {
+                    Some(exponent)
+                        if u64::from(exponent).saturating_mul(x.bits()) <= MAX_EXACT_BITS =>
+                    {
+                        Approx::Exact(x.pow(exponent))
+                    }
+                    _ => Approx::Float(a.to_f64().powf(b.to_f64())),
+                })
+            },
+            f64::powf,
+        )
+    }
+
+    fn neg(&self, a: &Value) -> Result<Value, KalcError> {
+        Ok(a.neg())
+    }
+
+    fn factorial(&self, n: &Value) -> Result<Value, KalcError> {
+        match n {
+            Approx::Exact(i) => exact_factorial(i).map(Approx::Exact),
+            Approx::Float(n) => float_factorial(*n).map(Approx::Float),
+        }
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Option<Ordering> {
+        Approx::compare(a, b)
+    }
+
+    fn truth(&self, holds: bool) -> Value {
+        Approx::Exact(BigInt::from(holds as u8))
+    }
+
+    fn is_zero(&self, value: &Value) -> bool {
+        value.is_zero()
+    }
+
+    fn is_negative(&self, value: &Value) -> bool {
+        value.is_negative()
+    }
+
+    fn is_finite(&self, value: &Value) -> bool {
+        value.is_finite()
+    }
+
+    fn builtin(&self, name: &str, args: &[Value]) -> Option<Value> {
+        let args = args.iter().map(Approx::exact).collect::<Option<Vec<_>>>()?;
+        integer_builtin(name, &args).map(Approx::Exact)
+    }
+}
+
+/// `exact` unless the divisor `y` is zero, in which case the float result of
+/// `a` and `b` carries the infinity or NaN on.
+fn integral(
+    y: &BigInt,
+    exact: impl FnOnce() -> BigInt,
+    a: &Value,
+    b: &Value,
+    float: fn(f64, f64) -> f64,
+) -> Value {
+    if y.is_zero() {
+        Approx::Float(float(a.to_f64(), b.to_f64()))
+    } else {
+        Approx::Exact(exact())
+    }
+}
+
+/// Built-ins whose result is an integer whenever their arguments are.
+pub(super) fn integer_builtin(name: &str, args: &[&BigInt]) -> Option<BigInt> {
+    let x = args[0];
+    Some(match name {
+        "abs" => x.abs(),
+        "floor" | "ceil" | "round" | "trunc" => x.clone(),
+        "sign" => x.signum(),
+        "min" => args.iter().copied().min()?.clone(),
+        "max" => args.iter().copied().max()?.clone(),
+        "gcd" => args.iter().fold(BigInt::zero(), |a, b| a.gcd(b)),
+        "lcm" => args.iter().fold(BigInt::from(1), |a, b| a.lcm(b)),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(n: i64) -> Value {
+        Approx::Exact(BigInt::from(n))
+    }
+
+    /// The same rules as for floats, computed exactly.
+    #[test]
+    fn division_sign_rules() {
+        let cases = [
+            (7, 3, 1, 1, 2),
+            (-7, 3, -1, 2, -3),
+            (7, -3, 1, 1, -3),
+            (-7, -3, -1, 2, 2),
+            (-7, 2, -1, 1, -4),
+            (6, -3, 0, 0, -2),
+        ];
+        for (a, b, rem, modulo, floor) in cases {
+            let (a, b) = (int(a), int(b));
+            assert_eq!(Integer.rem(&a, &b).unwrap(), int(rem));
+            assert_eq!(Integer.modulo(&a, &b).unwrap(), int(modulo));
+            assert_eq!(Integer.floor_div(&a, &b).unwrap(), int(floor));
+        }
+    }
+
+    #[test]
+    fn huge_operands_stay_exact() {
+        let big = Integer.parse("100000000000000000000001").unwrap();
+        assert_eq!(Integer.rem(&big, &int(-7)).unwrap(), int(6));
+        assert_eq!(
+            Integer
+                .modulo(&Integer.neg(&big).unwrap(), &int(7))
+                .unwrap(),
+            int(1)
+        );
+    }
+
+    #[test]
+    fn huge_exponents_overflow() {
+        assert!(matches!(
+            Integer.parse("1e99999999999"),
+            Err(KalcError::Overflow(None))
+        ));
+        assert!(matches!(
+            Integer.parse("1e9999999"),
+            Err(KalcError::Overflow(None))
+        ));
+        assert_eq!(Integer.parse("1e-99999999999").unwrap(), Approx::Float(0.0));
+    }
+}
@@ -0,0 +1,356 @@
+//! The number representations kalc can evaluate with. Each backend
+//! implements [`Numeric`], and the evaluator is generic over it, so the
+//! representation is picked at runtime without the parser knowing about it.
+
+mod complex;
+mod decimal;
+mod fixed;
+mod float;
+mod integer;
+mod rational;
+mod units;
+
+use std::{cmp::Ordering, fmt};
+
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+
+use crate::{calendar::Calendar, error::KalcError, functions::Builtin, parser::Op};
+
+pub use complex::Complex;
+pub use decimal::{DEFAULT_PRECISION, Decimal, MAX_PRECISION};
+pub use fixed::{Fixed, WIDTHS};
+pub use float::Float;
+pub use integer::Integer;
+pub use rational::Exact;
+pub use units::Units;
+
+/// Largest size, in bits, of an integer, numerator or denominator an exact
+/// power may produce, a little over a million decimal digits.
+const MAX_EXACT_BITS: u64 = 4_000_000;
+
+/// Largest `n` whose factorial is computed exactly, about 36,000 digits.
+const MAX_FACTORIAL: u64 = 10_000;
+
+/// How values are written out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Format {
+    /// Write fractions larger than one as mixed numbers, `1 1/4` rather than
+    /// `5/4`.
+    pub mixed: bool,
+    /// Write complex numbers as a magnitude and an angle in degrees,
+    /// `5∠53.13°` rather than `3 + 4i`.
+    pub polar: bool,
+    /// Write integers in this base, `0xff` rather than `255`.
+    pub radix: Option<u32>,
+    /// Write integers in decimal, hexadecimal and binary at once,
+    /// `255 (0xff, 0b11111111)`.
+    pub all_bases: bool,
+}
+
+/// Arithmetic on one representation of numbers. The backend holds whatever
+/// settings it computes with, such as a decimal precision.
+///
+/// Operations return [`KalcError`] for results the backend refuses to
+/// produce; infinities and NaNs a backend can represent are checked by the
+/// evaluator.
+pub trait Numeric {
+    type Value: Clone + fmt::Debug;
+
+    /// Converts a number literal as written in the input.
+    fn parse(&self, text: &str) -> Result<Self::Value, KalcError>;
+    /// The value of a name the backend knows itself, such as the unit `km`
+    /// or `today`, for backends with units of measurement.
+    fn named(&self, _name: &str) -> Option<Result<Self::Value, KalcError>> {
+        None
+    }
+    /// Converts a date, time of day or duration literal.
+    fn calendar(&self, _kind: Calendar, text: &str) -> Result<Self::Value, KalcError> {
+        Err(KalcError::Domain(format!(
+            "{} needs support for dates and times",
+            text
+        )))
+    }
+    /// `value to unit`: `value` expressed in `target`, a quantity written
+    /// as `unit` in the input.
+    fn convert(
+        &self,
+        _value: &Self::Value,
+        _target: &Self::Value,
+        unit: &str,
+    ) -> Result<Self::Value, KalcError> {
+        Err(KalcError::UnknownIdentifier(unit.to_string()))
+    }
+    /// `value to hex`: `value` marked to be written in base `radix`, for
+    /// backends that keep track of how values are written.
+    fn radix(&self, _value: &Self::Value, radix: u32) -> Result<Self::Value, KalcError> {
+        Err(KalcError::Domain(format!(
+            "Writing numbers in base {} is not supported",
+            radix
+        )))
+    }
+    /// Converts an imaginary literal, given without its `i`.
+    fn imaginary(&self, text: &str) -> Result<Self::Value, KalcError> {
+        Err(KalcError::Domain(format!(
+            "Imaginary numbers such as {}i need --complex",
+            text
+        )))
+    }
+    /// Converts a float, such as a constant or the result of a function only
+    /// implemented for floats.
+    fn float(&self, n: f64) -> Result<Self::Value, KalcError>;
+    fn to_f64(&self, value: &Self::Value) -> f64;
+    /// The value as an integer, when it is one.
+    fn integer(&self, value: &Self::Value) -> Option<BigInt> {
+        let n = self.to_f64(value);
+        (n.fract() == 0.0).then(|| BigInt::from_f64(n)).flatten()
+    }
+    fn format(&self, value: &Self::Value, format: Format) -> String;
+    /// Writes the value in base `radix`, `None` when it is not an integer.
+    fn format_in(&self, value: &Self::Value, radix: u32) -> Option<String> {
+        self.integer(value).map(|n| format_radix(&n, radix))
+    }
+
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    fn sub(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    fn div(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    /// `a // b`: floor of the true quotient.
+    fn floor_div(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    /// `a % b`: remainder of truncated division, taking the sign of `a`.
+    fn rem(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    /// `a mod b`: Euclidean modulo, never negative.
+    fn modulo(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    fn pow(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError>;
+    fn neg(&self, a: &Self::Value) -> Result<Self::Value, KalcError>;
+    /// `a & b`, `a << b` and the other bitwise operators.
+    fn bitwise(
+        &self,
+        _op: &Op,
+        _a: &Self::Value,
+        _b: &Self::Value,
+    ) -> Result<Self::Value, KalcError> {
+        Err(bitwise_needs_int())
+    }
+    /// `~a`.
+    fn bit_not(&self, _a: &Self::Value) -> Result<Self::Value, KalcError> {
+        Err(bitwise_needs_int())
+    }
+    /// `n!`.
+    fn factorial(&self, n: &Self::Value) -> Result<Self::Value, KalcError>;
+    /// Orders two values, `None` when they cannot be ordered, as with NaN.
+    fn compare(&self, a: &Self::Value, b: &Self::Value) -> Option<Ordering>;
+    /// The value of a comparison: 1 when it holds and 0 otherwise.
+    fn truth(&self, holds: bool) -> Self::Value;
+
+    fn is_zero(&self, value: &Self::Value) -> bool;
+    fn is_negative(&self, value: &Self::Value) -> bool;
+    /// Whether the value is neither infinite nor NaN.
+    fn is_finite(&self, _value: &Self::Value) -> bool {
+        true
+    }
+
+    fn is_nan(&self, value: &Self::Value) -> bool {
+        self.to_f64(value).is_nan()
+    }
+
+    /// The built-in `name` computed in the backend's own representation,
+    /// for the arguments it has an implementation for. Anything else goes
+    /// through floats.
+    fn builtin(&self, _name: &str, _args: &[Self::Value]) -> Option<Self::Value> {
+        None
+    }
+
+    /// Whether `result` had to be computed as a float although `operands`
+    /// were exact. Only backends promising exact results report this.
+    fn fell_back(&self, _result: &Self::Value, _operands: &[Self::Value]) -> bool {
+        false
+    }
+
+    /// Whether falling back to floats is an error rather than a warning.
+    fn strict(&self) -> bool {
+        false
+    }
+
+    fn binary(&self, op: &Op, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, KalcError> {
+        apply(self, op, a, b)
+    }
+
+    /// Calls a built-in, using the backend's own implementation when it has
+    /// one and going through floats otherwise.
+    fn call(
+        &self,
+        builtin: &Builtin,
+        args: &[Self::Value],
+        ieee: bool,
+    ) -> Result<Self::Value, KalcError> {
+        let floats = args.iter().map(|arg| self.to_f64(arg)).collect::<Vec<_>>();
+        builtin.check_domain(&floats, ieee)?;
+        match self.builtin(builtin.name, args) {
+            Some(result) => Ok(result),
+            None => self.float(builtin.call(&floats)?),
+        }
+    }
+}
+
+/// Applies a binary operator with the operations of `backend`, for backends
+/// overriding [`Numeric::binary`] to fall back on.
+fn apply<N: Numeric + ?Sized>(
+    backend: &N,
+    op: &Op,
+    a: &N::Value,
+    b: &N::Value,
+) -> Result<N::Value, KalcError> {
+    let compare =
+        |holds: fn(Ordering) -> bool| Ok(backend.truth(backend.compare(a, b).is_some_and(holds)));
+    match op {
+        Op::Add => backend.add(a, b),
+        Op::Sub => backend.sub(a, b),
+        Op::Mul => backend.mul(a, b),
+        Op::Div => backend.div(a, b),
+        Op::FloorDiv => backend.floor_div(a, b),
+        Op::Rem => backend.rem(a, b),
+        Op::Mod => backend.modulo(a, b),
+        Op::Pow => backend.pow(a, b),
+        Op::Lt => compare(Ordering::is_lt),
+        Op::Le => compare(Ordering::is_le),
+        Op::Gt => compare(Ordering::is_gt),
+        Op::Ge => compare(Ordering::is_ge),
+        Op::Eq => compare(Ordering::is_eq),
+        // NaN is unequal to everything, itself included.
+        Op::Ne => Ok(backend.truth(backend.compare(a, b) != Some(Ordering::Equal))),
+        Op::BitAnd | Op::BitOr | Op::BitXor | Op::Shl | Op::Shr | Op::UShr => {
+            backend.bitwise(op, a, b)
+        }
+    }
+}
+
+/// A value of a backend that stays exact for as long as the operations on it
+/// allow, and continues as a float once they do not.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approx<T> {
+    Exact(T),
+    Float(f64),
+}
+
+impl<T: Clone + ToPrimitive + Signed + Ord> Approx<T> {
+    fn to_f64(&self) -> f64 {
+        match self {
+            Approx::Exact(x) => to_f64(x),
+            Approx::Float(n) => *n,
+        }
+    }
+
+    fn exact(&self) -> Option<&T> {
+        match self {
+            Approx::Exact(x) => Some(x),
+            Approx::Float(_) => None,
+        }
+    }
+
+    /// Applies `exact` when both operands are exact and `float` to their
+    /// float values otherwise.
+    fn combine(
+        a: &Self,
+        b: &Self,
+        exact: impl FnOnce(&T, &T) -> Result<Self, KalcError>,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<Self, KalcError> {
+        match (a, b) {
+            (Approx::Exact(a), Approx::Exact(b)) => exact(a, b),
+            _ => Ok(Approx::Float(float(a.to_f64(), b.to_f64()))),
+        }
+    }
+
+    fn neg(&self) -> Self {
+        match self {
+            Approx::Exact(x) => Approx::Exact(-x.clone()),
+            Approx::Float(n) => Approx::Float(-n),
+        }
+    }
+
+    fn compare(a: &Self, b: &Self) -> Option<Ordering> {
+        match (a, b) {
+            (Approx::Exact(a), Approx::Exact(b)) => Some(a.cmp(b)),
+            _ => a.to_f64().partial_cmp(&b.to_f64()),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Approx::Exact(x) => x.is_zero(),
+            Approx::Float(n) => *n == 0.0,
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        match self {
+            Approx::Exact(x) => x.is_negative(),
+            Approx::Float(n) => *n < 0.0,
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        match self {
+            Approx::Exact(_) => true,
+            Approx::Float(n) => n.is_finite(),
+        }
+    }
+}
+
+/// Writes `n` in base `radix`, with the prefix it can be read back with for
+/// bases 2, 8 and 16: `0xff`, `-0b101`, `zz (base 36)`.
+fn format_radix(n: &BigInt, radix: u32) -> String {
+    let sign = if n.is_negative() { "-" } else { "" };
+    let digits = n.magnitude().to_str_radix(radix);
+    match radix {
+        2 => format!("{}0b{}", sign, digits),
+        8 => format!("{}0o{}", sign, digits),
+        10 => n.to_string(),
+        16 => format!("{}0x{}", sign, digits),
+        _ => format!("{}{} (base {})", sign, digits, radix),
+    }
+}
+
+fn to_f64(x: &impl ToPrimitive) -> f64 {
+    x.to_f64().unwrap_or(f64::NAN)
+}
+
+/// `divisor`, unless it is zero. Only floats have an infinity, so division
+/// by zero is an error in every other backend, even with `--ieee`.
+fn nonzero<T: Zero>(divisor: &T) -> Result<&T, KalcError> {
+    if divisor.is_zero() {
+        Err(KalcError::DivisionByZero)
+    } else {
+        Ok(divisor)
+    }
+}
+
+fn bitwise_needs_int() -> KalcError {
+    KalcError::Domain("Bitwise operators need fixed-width integers, given with --int".to_string())
+}
+
+fn not_natural() -> KalcError {
+    KalcError::Domain("Factorial expects a non-negative integer".to_string())
+}
+
+/// `n!` as a float, for integral `n`.
+fn float_factorial(n: f64) -> Result<f64, KalcError> {
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(not_natural());
+    }
+    // Anything past 170! is infinite anyway.
+    Ok((2..=n.min(171.0) as u64).map(|k| k as f64).product())
+}
+
+/// `n!` computed exactly, refused once it gets too large.
+fn exact_factorial(n: &BigInt) -> Result<BigInt, KalcError> {
+    if n.is_negative() {
+        return Err(not_natural());
+    }
+    let n = n
+        .to_u64()
+        .filter(|n| *n <= MAX_FACTORIAL)
+        .ok_or(KalcError::Overflow(None))?;
+    Ok((2..=n).fold(BigInt::from(1), |acc, k| acc * k))
+}
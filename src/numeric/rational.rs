@@ -0,0 +1,242 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use super::{
+    Approx, Format, MAX_EXACT_BITS, Numeric, exact_factorial,
+    float::{floor_div, format_f64},
+    float_factorial,
+    integer::integer_builtin,
+    nonzero, not_natural, to_f64,
+};
+use crate::error::KalcError;
+
+/// Big rationals, so that `1/3 x 3` is exactly 1. Operations without a
+/// rational result continue with floats, or fail when `strict` is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Exact {
+    pub strict: bool,
+}
+
+type Value = Approx<BigRational>;
+
+impl Numeric for Exact {
+    type Value = Value;
+
+    fn parse(&self, text: &str) -> Result<Value, KalcError> {
+        let d = BigDecimal::from_str(text)
+            .map_err(|_| KalcError::Lex(format!("Invalid number: {}", text)))?;
+        // The power of ten an exponent such as 1e999999999 stands for.
+        if d.fractional_digit_count().unsigned_abs() * 10 / 3 > MAX_EXACT_BITS {
+            return Err(KalcError::Overflow(None));
+        }
+        Ok(Approx::Exact(rational_from_decimal(&d)))
+    }
+
+    /// Floats stay floats, since their digits are not exact.
+    fn float(&self, n: f64) -> Result<Value, KalcError> {
+        Ok(Approx::Float(n))
+    }
+
+    fn to_f64(&self, value: &Value) -> f64 {
+        value.to_f64()
+    }
+
+    fn integer(&self, value: &Value) -> Option<BigInt> {
+        match value {
+            Approx::Exact(r) => r.is_integer().then(|| r.to_integer()),
+            Approx::Float(n) => (n.fract() == 0.0).then(|| BigInt::from_f64(*n)).flatten(),
+        }
+    }
+
+    fn format(&self, value: &Value, format: Format) -> String {
+        match value {
+            Approx::Exact(r) if format.mixed && r.abs() > BigRational::one() => {
+                let whole = r.trunc();
+                let fraction = (r - &whole).abs();
+                if fraction.is_zero() {
+                    whole.to_string()
+                } else {
+                    format!("{} {}", whole, fraction)
+                }
+            }
+            Approx::Exact(r) => r.to_string(),
+            Approx::Float(n) => format_f64(*n),
+        }
+    }
+
+    fn add(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, |a, b| Ok(Approx::Exact(a + b)), |a, b| a + b)
+    }
+
+    fn sub(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, |a, b| Ok(Approx::Exact(a - b)), |a, b| a - b)
+    }
+
+    fn mul(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, |a, b| Ok(Approx::Exact(a * b)), |a, b| a * b)
+    }
+
+    fn div(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |a, b| Ok(Approx::Exact(a / nonzero(b)?)),
+            |a, b| a / b,
+        )
+    }
+
+    fn floor_div(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |a, b| Ok(Approx::Exact((a / nonzero(b)?).floor())),
+            floor_div,
+        )
+    }
+
+    fn rem(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |a, b| remainder(a, b).map(Approx::Exact),
+            |a, b| a % b,
+        )
+    }
+
+    fn modulo(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(
+            a,
+            b,
+            |a, b| {
+                let r = remainder(a, b)?;
+                Ok(Approx::Exact(if r.is_negative() { r + b.abs() } else { r }))
+            },
+            f64::rem_euclid,
+        )
+    }
+
+    fn pow(&self, a: &Value, b: &Value) -> Result<Value, KalcError> {
+        Approx::combine(a, b, power, f64::powf)
+    }
+
+    fn neg(&self, a: &Value) -> Result<Value, KalcError> {
+        Ok(a.neg())
+    }
+
+    fn factorial(&self, n: &Value) -> Result<Value, KalcError> {
+        match n {
+            Approx::Exact(r) if r.is_integer() => exact_factorial(&r.to_integer())
+                .map(|n| Approx::Exact(BigRational::from_integer(n))),
+            Approx::Exact(_) => Err(not_natural()),
+            Approx::Float(n) => float_factorial(*n).map(Approx::Float),
+        }
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Option<Ordering> {
+        Approx::compare(a, b)
+    }
+
+    fn truth(&self, holds: bool) -> Value {
+        Approx::Exact(BigRational::from_integer(BigInt::from(holds as u8)))
+    }
+
+    fn is_zero(&self, value: &Value) -> bool {
+        value.is_zero()
+    }
+
+    fn is_negative(&self, value: &Value) -> bool {
+        value.is_negative()
+    }
+
+    fn is_finite(&self, value: &Value) -> bool {
+        value.is_finite()
+    }
+
+    /// Built-ins that have an exact rational counterpart, at least for some
+    /// arguments.
+    fn builtin(&self, name: &str, args: &[Value]) -> Option<Value> {
+        let args = args.iter().map(Approx::exact).collect::<Option<Vec<_>>>()?;
+        let x = args[0];
+        Some(Approx::Exact(match name {
+            "sqrt" => root(x, 2)?,
+            "cbrt" => root(x, 3)?,
+            "abs" => x.abs(),
+            "floor" => x.floor(),
+            "ceil" => x.ceil(),
+            "round" => x.round(),
+            "trunc" => x.trunc(),
+            "sign" => x.signum(),
+            "min" => args.iter().copied().min()?.clone(),
+            "max" => args.iter().copied().max()?.clone(),
+            "gcd" | "lcm" => {
+                let integers = args
+                    .iter()
+                    .map(|arg| arg.is_integer().then(|| arg.to_integer()))
+                    .collect::<Option<Vec<_>>>()?;
+                let integers = integers.iter().collect::<Vec<_>>();
+                BigRational::from_integer(integer_builtin(name, &integers)?)
+            }
+            _ => return None,
+        }))
+    }
+
+    fn fell_back(&self, result: &Value, operands: &[Value]) -> bool {
+        result.exact().is_none() && operands.iter().all(|n| n.exact().is_some())
+    }
+
+    fn strict(&self) -> bool {
+        self.strict
+    }
+}
+
+/// The exact value of a decimal, literals included.
+fn rational_from_decimal(d: &BigDecimal) -> BigRational {
+    let (digits, scale) = d.as_bigint_and_exponent();
+    let power = BigInt::from(10).pow(scale.unsigned_abs() as u32);
+    if scale >= 0 {
+        BigRational::new(digits, power)
+    } else {
+        BigRational::from_integer(digits * power)
+    }
+}
+
+fn remainder(a: &BigRational, b: &BigRational) -> Result<BigRational, KalcError> {
+    Ok(a - b * (a / nonzero(b)?).trunc())
+}
+
+/// Integer powers, and fractional ones whose root comes out rational, are
+/// exact; any other power is computed as a float.
+fn power(a: &BigRational, b: &BigRational) -> Result<Value, KalcError> {
+    if a.is_zero() && b.is_negative() {
+        return Err(KalcError::DivisionByZero);
+    }
+    let base = b.denom().to_u32().and_then(|n| root(a, n));
+    if let (Some(base), Some(exponent)) = (base, b.numer().to_i32()) {
+        let bits = base.numer().bits().max(base.denom().bits());
+        if u64::from(exponent.unsigned_abs()).saturating_mul(bits) > MAX_EXACT_BITS {
+            return Err(KalcError::Overflow(None));
+        }
+        return Ok(Approx::Exact(base.pow(exponent)));
+    }
+
+    Ok(Approx::Float(to_f64(a).powf(to_f64(b))))
+}
+
+/// The `n`th root of `x` when it is rational.
+fn root(x: &BigRational, n: u32) -> Option<BigRational> {
+    if n == 1 {
+        return Some(x.clone());
+    }
+    if x.is_negative() && n.is_multiple_of(2) {
+        return None;
+    }
+    let root = |i: &BigInt| {
+        let root = i.nth_root(n);
+        (root.pow(n) == *i).then_some(root)
+    };
+    Some(BigRational::new(root(x.numer())?, root(x.denom())?))
+}
@@ -0,0 +1,843 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use super::{Format, Numeric, apply};
+use crate::{
+    calendar::{self, Calendar},
+    error::KalcError,
+    functions::Builtin,
+    parser::Op,
+    rates::{self, Rates},
+    units::{self, Dimension},
+};
+
+/// Adds units of measurement to another backend, which computes the
+/// magnitudes. Quantities are kept in SI base units along with their
+/// dimension, so that `3 km + 200 m` needs no conversion and `3 m + 2 s` can
+/// be rejected. Amounts of money are kept in their own currency and only
+/// converted with the rates in `rates` when needed.
+#[derive(Debug, Clone, Default)]
+pub struct Units<N> {
+    inner: N,
+    rates: Option<Rates>,
+}
+
+impl<N> Units<N> {
+    pub fn new(inner: N, rates: Option<Rates>) -> Self {
+        Self { inner, rates }
+    }
+}
+
+/// A magnitude along with its dimension.
+#[derive(Debug, Clone)]
+pub struct Quantity<V> {
+    /// The magnitude in SI base units.
+    value: V,
+    dimension: Dimension,
+    /// The unit the quantity was given or converted to, printed in place of
+    /// SI base units, and its size in them.
+    unit: Option<Rc<(String, V)>>,
+    /// For a date, time or duration, which one it is. Dates and times are
+    /// seconds since the epoch.
+    calendar: Option<Calendar>,
+    /// The base the quantity was converted to with `to hex` and the like.
+    radix: Option<u32>,
+}
+
+impl<V> Quantity<V> {
+    fn plain(value: V) -> Self {
+        Self {
+            value,
+            dimension: Dimension::NONE,
+            unit: None,
+            calendar: None,
+            radix: None,
+        }
+    }
+
+    fn with(self, dimension: Dimension, unit: Option<Rc<(String, V)>>) -> Self {
+        Self {
+            dimension,
+            unit,
+            ..self
+        }
+    }
+
+    fn dated(self, calendar: Option<Calendar>) -> Self {
+        Self { calendar, ..self }
+    }
+
+    /// Whether this is a date or time rather than an amount.
+    fn is_point(&self) -> bool {
+        self.calendar.is_some_and(Calendar::is_point)
+    }
+}
+
+type Value<N> = Quantity<<N as Numeric>::Value>;
+
+fn mismatch<V>(a: &Quantity<V>, b: &Quantity<V>) -> KalcError {
+    KalcError::DimensionMismatch {
+        left: a.dimension.unit_name(),
+        right: b.dimension.unit_name(),
+    }
+}
+
+fn same<V>(a: &Quantity<V>, b: &Quantity<V>) -> Result<(), KalcError> {
+    if a.dimension == b.dimension {
+        Ok(())
+    } else {
+        Err(mismatch(a, b))
+    }
+}
+
+/// Rejects amounts in two different currencies, which only combine once one
+/// of them is converted with `to`.
+fn one_currency<V>(a: &Quantity<V>, b: &Quantity<V>) -> Result<(), KalcError> {
+    match (a.dimension.currency(), b.dimension.currency()) {
+        (Some(left), Some(right)) if left != right => Err(mismatch(a, b)),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects dates and times in operations only amounts support.
+fn undated<V>(value: &Quantity<V>) -> Result<(), KalcError> {
+    if value.is_point() {
+        Err(KalcError::Domain(
+            "Dates and times can only be compared, subtracted or shifted by a duration".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn without_units<V>(what: &str, value: &Quantity<V>) -> Result<(), KalcError> {
+    if value.dimension.is_none() {
+        Ok(())
+    } else {
+        Err(KalcError::Domain(format!(
+            "{} expects a number without units",
+            what
+        )))
+    }
+}
+
+impl<N: Numeric> Units<N> {
+    fn plain(&self, value: Result<N::Value, KalcError>) -> Result<Value<N>, KalcError> {
+        value.map(Quantity::plain)
+    }
+
+    /// Parses a unit factor, either a decimal or a fraction such as `5/18`.
+    fn factor(&self, text: &str) -> Option<N::Value> {
+        match text.split_once('/') {
+            Some((numerator, denominator)) => {
+                let numerator = self.inner.parse(numerator).ok()?;
+                let denominator = self.inner.parse(denominator).ok()?;
+                self.inner.div(&numerator, &denominator).ok()
+            }
+            None => self.inner.parse(text).ok(),
+        }
+    }
+
+    /// One unit of the currency `code`, if the rates have it.
+    fn currency(&self, code: &str) -> Option<Result<Value<N>, KalcError>> {
+        let Some(rates) = &self.rates else {
+            return Some(Err(KalcError::Usage(format!(
+                "Currency {} needs a rates file, given with --rates or {}",
+                code,
+                rates::RATES_VAR
+            ))));
+        };
+        rates.get(code)?;
+        Some(self.inner.parse("1").map(|one| Quantity {
+            value: one.clone(),
+            dimension: Dimension::money(code),
+            unit: Some(Rc::new((code.to_string(), one))),
+            calendar: None,
+            radix: None,
+        }))
+    }
+
+    /// The rate of the currency `code`, as read from the rates file.
+    fn rate(&self, code: &str) -> Result<N::Value, KalcError> {
+        self.rates
+            .as_ref()
+            .and_then(|rates| rates.get(code))
+            .and_then(|rate| self.factor(rate))
+            .ok_or_else(|| KalcError::UnknownIdentifier(code.to_string()))
+    }
+
+    /// The magnitude of `value` with its money in the currency `code`. Each
+    /// power of money is multiplied by the rate of `code` and divided by the
+    /// rate of its own currency, rounding once.
+    fn rebase(&self, value: &Value<N>, code: &str) -> Result<N::Value, KalcError> {
+        let Some(from) = value.dimension.currency().filter(|&from| from != code) else {
+            return Ok(value.value.clone());
+        };
+        let exponent = value.dimension.money_exponent();
+        let power = self.inner.parse(&exponent.unsigned_abs().to_string())?;
+        let (to, from) = (self.rate(code)?, self.rate(from)?);
+        let (numerator, denominator) = if exponent < 0 { (from, to) } else { (to, from) };
+        let numerator = self.inner.pow(&numerator, &power)?;
+        let denominator = self.inner.pow(&denominator, &power)?;
+        let scaled = self.inner.mul(&value.value, &numerator)?;
+        self.inner.div(&scaled, &denominator)
+    }
+
+    /// `point` moved forward, or back when `subtract` is set, by `duration`.
+    /// A date moved by a fraction of a day gets a time of day.
+    fn shift(
+        &self,
+        point: &Value<N>,
+        duration: &Value<N>,
+        subtract: bool,
+    ) -> Result<Value<N>, KalcError> {
+        if duration.dimension != units::TIME || duration.is_point() {
+            return Err(KalcError::Domain(
+                "A date or time can only be shifted by a duration".to_string(),
+            ));
+        }
+        let value = if subtract {
+            self.inner.sub(&point.value, &duration.value)?
+        } else {
+            self.inner.add(&point.value, &duration.value)?
+        };
+        let whole_days = self.inner.to_f64(&duration.value) % 86400.0 == 0.0;
+        let calendar = match point.calendar {
+            Some(Calendar::Date) if !whole_days => Some(Calendar::DateTime),
+            calendar => calendar,
+        };
+        if !calendar::in_range(self.inner.to_f64(&value)) {
+            return Err(KalcError::Overflow(Some(
+                "Date or time is out of range".to_string(),
+            )));
+        }
+        Ok(Quantity::plain(value)
+            .with(point.dimension, None)
+            .dated(calendar))
+    }
+
+    /// A date or time counted in seconds since the epoch.
+    fn moment(&self, calendar: Calendar, seconds: i64) -> Result<Value<N>, KalcError> {
+        let value = self.inner.parse(&seconds.to_string())?;
+        Ok(Quantity::plain(value)
+            .with(units::TIME, None)
+            .dated(Some(calendar)))
+    }
+
+    /// Where the temperature scale of `unit` starts, in kelvin, unless it
+    /// starts at absolute zero.
+    fn offset(&self, unit: Option<&Rc<(String, N::Value)>>) -> Option<N::Value> {
+        let (_, unit) = units::lookup(&unit?.0)?;
+        self.factor(unit.offset?)
+    }
+
+    /// The magnitude of `value` in its own unit, if it has one.
+    fn in_unit<'a>(&self, value: &'a Value<N>) -> Option<(N::Value, &'a str)> {
+        let unit = value.unit.as_ref()?;
+        let magnitude = self.inner.div(&value.value, &unit.1).ok()?;
+        Some((magnitude, &unit.0))
+    }
+
+    /// Writes a magnitude, in the bases `format` asks for when it is an
+    /// integer.
+    fn number(&self, n: &N::Value, format: Format) -> String {
+        let bases = format
+            .all_bases
+            .then(|| self.inner.format_in(n, 16).zip(self.inner.format_in(n, 2)))
+            .flatten();
+        if let Some((hex, bin)) = bases {
+            return format!("{} ({}, {})", self.inner.format(n, format), hex, bin);
+        }
+        format
+            .radix
+            .and_then(|radix| self.inner.format_in(n, radix))
+            .unwrap_or_else(|| self.inner.format(n, format))
+    }
+}
+
+impl<N: Numeric> Numeric for Units<N> {
+    type Value = Value<N>;
+
+    fn parse(&self, text: &str) -> Result<Value<N>, KalcError> {
+        self.plain(self.inner.parse(text))
+    }
+
+    fn imaginary(&self, text: &str) -> Result<Value<N>, KalcError> {
+        self.plain(self.inner.imaginary(text))
+    }
+
+    fn float(&self, n: f64) -> Result<Value<N>, KalcError> {
+        self.plain(self.inner.float(n))
+    }
+
+    fn to_f64(&self, value: &Value<N>) -> f64 {
+        self.inner.to_f64(&value.value)
+    }
+
+    /// Amounts of money are followed by the date of the rates they were
+    /// converted with.
+    fn format(&self, value: &Value<N>, format: Format) -> String {
+        let format = Format {
+            radix: value.radix.or(format.radix),
+            ..format
+        };
+        let seconds = self.inner.to_f64(&value.value);
+        let text = if let Some(text) = value
+            .calendar
+            .and_then(|kind| calendar::format(kind, seconds))
+        {
+            text
+        } else if let Some((magnitude, unit)) = self.in_unit(value) {
+            format!("{} {}", self.number(&magnitude, format), unit)
+        } else if value.dimension.is_none() {
+            self.number(&value.value, format)
+        } else {
+            let magnitude = self.number(&value.value, format);
+            format!("{} {}", magnitude, value.dimension.unit_name())
+        };
+        match &self.rates {
+            Some(rates) if value.dimension.currency().is_some() => {
+                format!("{} (rates of {})", text, rates.date)
+            }
+            _ => text,
+        }
+    }
+
+    fn named(&self, name: &str) -> Option<Result<Value<N>, KalcError>> {
+        match name {
+            "today" => return Some(self.moment(Calendar::Date, calendar::midnight())),
+            "now" => return Some(self.moment(Calendar::DateTime, calendar::now())),
+            _ => {}
+        }
+        if rates::is_code(name) && units::lookup(name).is_none() {
+            return self.currency(name);
+        }
+        let (prefix, unit) = units::lookup(name)?;
+        let mut factor = self.factor(unit.factor)?;
+        if let Some(prefix) = prefix {
+            factor = self.inner.mul(&self.factor(prefix.factor)?, &factor).ok()?;
+        }
+        Some(Ok(Quantity {
+            value: factor.clone(),
+            dimension: unit.dimension,
+            unit: Some(Rc::new((name.to_string(), factor))),
+            calendar: None,
+            radix: None,
+        }))
+    }
+
+    fn radix(&self, value: &Value<N>, radix: u32) -> Result<Value<N>, KalcError> {
+        Ok(Quantity {
+            radix: Some(radix),
+            ..value.clone()
+        })
+    }
+
+    fn calendar(&self, kind: Calendar, text: &str) -> Result<Value<N>, KalcError> {
+        if kind != Calendar::Duration {
+            let seconds = calendar::timestamp(kind, text)
+                .ok_or_else(|| KalcError::Domain(format!("Invalid date or time: {}", text)))?;
+            return self.moment(kind, seconds);
+        }
+        let (amount, unit) = text.split_at(text.len() - 1);
+        let size = unit.chars().next().and_then(calendar::duration_unit);
+        let size = self.inner.parse(size.unwrap_or("1"))?;
+        let value = self.inner.mul(&self.inner.parse(amount)?, &size)?;
+        Ok(Quantity::plain(value)
+            .with(units::TIME, None)
+            .dated(Some(Calendar::Duration)))
+    }
+
+    fn convert(
+        &self,
+        value: &Value<N>,
+        target: &Value<N>,
+        unit: &str,
+    ) -> Result<Value<N>, KalcError> {
+        undated(value)?;
+        if !value.dimension.converts_to(target.dimension) {
+            return Err(mismatch(value, target));
+        }
+        if self.inner.is_zero(&target.value) {
+            return Err(KalcError::DivisionByZero);
+        }
+        let mut magnitude = match target.dimension.currency() {
+            Some(code) => self.rebase(value, code)?,
+            None => value.value.clone(),
+        };
+        let unit = Rc::new((unit.to_string(), target.value.clone()));
+        // Temperatures in °C and °F count from the zero of their own scale,
+        // which moves when converting to another scale.
+        if value.dimension == units::TEMPERATURE {
+            let zero = self.inner.parse("0")?;
+            let from = self.offset(value.unit.as_ref()).unwrap_or(zero.clone());
+            let to = self.offset(Some(&unit)).unwrap_or(zero);
+            magnitude = self.inner.add(&magnitude, &self.inner.sub(&from, &to)?)?;
+        }
+        Ok(Quantity::plain(magnitude).with(target.dimension, Some(unit)))
+    }
+
+    /// A date or time moves by a duration added to it; two of them cannot
+    /// be added.
+    fn add(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        match (a.is_point(), b.is_point()) {
+            (true, true) => {
+                return Err(KalcError::Domain(
+                    "Cannot add two dates or times; subtract them for the time between".to_string(),
+                ));
+            }
+            (true, false) => return self.shift(a, b, false),
+            (false, true) => return self.shift(b, a, false),
+            (false, false) => {}
+        }
+        same(a, b)?;
+        let sum = self.inner.add(&a.value, &b.value)?;
+        let calendar = a
+            .unit
+            .is_none()
+            .then_some(a.calendar.or(b.calendar))
+            .flatten();
+        Ok(Quantity::plain(sum)
+            .with(a.dimension, a.unit.clone().or(b.unit.clone()))
+            .dated(calendar))
+    }
+
+    /// The difference of two dates or times is a duration.
+    fn sub(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        match (a.is_point(), b.is_point()) {
+            (true, true) => {
+                let difference = self.inner.sub(&a.value, &b.value)?;
+                return Ok(Quantity::plain(difference)
+                    .with(units::TIME, None)
+                    .dated(Some(Calendar::Duration)));
+            }
+            (true, false) => return self.shift(a, b, true),
+            (false, true) => {
+                return Err(KalcError::Domain(
+                    "Cannot subtract a date or time from a duration".to_string(),
+                ));
+            }
+            (false, false) => {}
+        }
+        same(a, b)?;
+        let difference = self.inner.sub(&a.value, &b.value)?;
+        let calendar = a
+            .unit
+            .is_none()
+            .then_some(a.calendar.or(b.calendar))
+            .flatten();
+        Ok(Quantity::plain(difference)
+            .with(a.dimension, a.unit.clone().or(b.unit.clone()))
+            .dated(calendar))
+    }
+
+    /// Scaling a quantity keeps its unit; the product of two quantities is
+    /// printed in SI units.
+    fn mul(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        undated(a)?;
+        undated(b)?;
+        one_currency(a, b)?;
+        let product = self.inner.mul(&a.value, &b.value)?;
+        let (unit, calendar) = match (a.dimension.is_none(), b.dimension.is_none()) {
+            (true, _) => (b.unit.clone(), b.calendar),
+            (false, true) => (a.unit.clone(), a.calendar),
+            (false, false) => (None, None),
+        };
+        Ok(Quantity::plain(product)
+            .with(a.dimension.mul(b.dimension), unit)
+            .dated(calendar))
+    }
+
+    fn div(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        undated(a)?;
+        undated(b)?;
+        one_currency(a, b)?;
+        let quotient = self.inner.div(&a.value, &b.value)?;
+        let unit = b.dimension.is_none().then(|| a.unit.clone()).flatten();
+        let calendar = b.dimension.is_none().then_some(a.calendar).flatten();
+        Ok(Quantity::plain(quotient)
+            .with(a.dimension.div(b.dimension), unit)
+            .dated(calendar))
+    }
+
+    fn floor_div(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        undated(a)?;
+        undated(b)?;
+        one_currency(a, b)?;
+        let quotient = self.inner.floor_div(&a.value, &b.value)?;
+        let unit = b.dimension.is_none().then(|| a.unit.clone()).flatten();
+        let calendar = b.dimension.is_none().then_some(a.calendar).flatten();
+        Ok(Quantity::plain(quotient)
+            .with(a.dimension.div(b.dimension), unit)
+            .dated(calendar))
+    }
+
+    fn rem(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        undated(a)?;
+        undated(b)?;
+        same(a, b)?;
+        let remainder = self.inner.rem(&a.value, &b.value)?;
+        Ok(Quantity::plain(remainder)
+            .with(a.dimension, a.unit.clone())
+            .dated(a.calendar))
+    }
+
+    fn modulo(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        undated(a)?;
+        undated(b)?;
+        same(a, b)?;
+        let remainder = self.inner.modulo(&a.value, &b.value)?;
+        Ok(Quantity::plain(remainder)
+            .with(a.dimension, a.unit.clone())
+            .dated(a.calendar))
+    }
+
+    /// Quantities with units can only be raised to integer powers, which
+    /// apply to their unit as well.
+    fn pow(&self, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        undated(a)?;
+        without_units("An exponent", b)?;
+        let power = self.inner.pow(&a.value, &b.value)?;
+        if a.dimension.is_none() {
+            return Ok(Quantity::plain(power));
+        }
+
+        let exponent = self.inner.to_f64(&b.value);
+        let dimension = (exponent.fract() == 0.0)
+            .then(|| a.dimension.pow(exponent as i32))
+            .flatten()
+            .ok_or_else(|| {
+                KalcError::Domain("Quantities with units need an integer exponent".to_string())
+            })?;
+        let unit = match &a.unit {
+            Some(unit) => {
+                let factor = self.inner.pow(&unit.1, &b.value)?;
+                let name = if unit.0.contains(['/', '·', '^']) {
+                    format!("({})^{}", unit.0, exponent)
+                } else {
+                    format!("{}^{}", unit.0, exponent)
+                };
+                Some(Rc::new((name, factor)))
+            }
+            None => None,
+        };
+        Ok(Quantity::plain(power).with(dimension, unit))
+    }
+
+    fn neg(&self, a: &Value<N>) -> Result<Value<N>, KalcError> {
+        Ok(Quantity {
+            value: self.inner.neg(&a.value)?,
+            ..a.clone()
+        }
+        .dated(a.calendar.filter(|kind| !kind.is_point())))
+    }
+
+    fn factorial(&self, n: &Value<N>) -> Result<Value<N>, KalcError> {
+        without_units("Factorial", n)?;
+        self.plain(self.inner.factorial(&n.value))
+    }
+
+    fn bitwise(&self, op: &Op, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        without_units("A bitwise operator", a)?;
+        without_units("A bitwise operator", b)?;
+        self.plain(self.inner.bitwise(op, &a.value, &b.value))
+    }
+
+    fn bit_not(&self, a: &Value<N>) -> Result<Value<N>, KalcError> {
+        without_units("A bitwise operator", a)?;
+        self.plain(self.inner.bit_not(&a.value))
+    }
+
+    fn compare(&self, a: &Value<N>, b: &Value<N>) -> Option<Ordering> {
+        self.inner.compare(&a.value, &b.value)
+    }
+
+    fn truth(&self, holds: bool) -> Value<N> {
+        Quantity::plain(self.inner.truth(holds))
+    }
+
+    fn is_zero(&self, value: &Value<N>) -> bool {
+        self.inner.is_zero(&value.value)
+    }
+
+    fn is_negative(&self, value: &Value<N>) -> bool {
+        self.inner.is_negative(&value.value)
+    }
+
+    fn is_finite(&self, value: &Value<N>) -> bool {
+        self.inner.is_finite(&value.value)
+    }
+
+    fn is_nan(&self, value: &Value<N>) -> bool {
+        self.inner.is_nan(&value.value)
+    }
+
+    fn fell_back(&self, result: &Value<N>, operands: &[Value<N>]) -> bool {
+        let operands = operands.iter().map(|n| n.value.clone()).collect::<Vec<_>>();
+        self.inner.fell_back(&result.value, &operands)
+    }
+
+    fn strict(&self) -> bool {
+        self.inner.strict()
+    }
+
+    /// Comparing quantities of different dimensions, or a date with a
+    /// duration, is an error rather than false.
+    fn binary(&self, op: &Op, a: &Value<N>, b: &Value<N>) -> Result<Value<N>, KalcError> {
+        if matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne) {
+            if a.is_point() != b.is_point() {
+                return Err(KalcError::Domain(
+                    "Cannot compare a date or time with a duration".to_string(),
+                ));
+            }
+            same(a, b)?;
+        }
+        apply(self, op, a, b)
+    }
+
+    /// Functions take plain numbers, except for those that make sense for
+    /// any quantity, such as `abs` and `max`, and roots of units with a
+    /// matching power.
+    fn call(
+        &self,
+        builtin: &Builtin,
+        args: &[Value<N>],
+        ieee: bool,
+    ) -> Result<Value<N>, KalcError> {
+        for arg in args {
+            undated(arg)?;
+        }
+        let values = args.iter().map(|arg| arg.value.clone()).collect::<Vec<_>>();
+        let first = &args[0];
+        let mut calendar = None;
+        let (dimension, unit) = match builtin.name {
+            _ if args.iter().all(|arg| arg.dimension.is_none()) => (Dimension::NONE, None),
+            "abs" | "min" | "max" | "hypot" | "re" | "im" | "conj" => {
+                if let Some(other) = args.iter().find(|arg| arg.dimension != first.dimension) {
+                    return Err(mismatch(first, other));
+                }
+                calendar = first.calendar;
+                (first.dimension, first.unit.clone())
+            }
+            "sign" | "arg" => (Dimension::NONE, None),
+            "sqrt" | "cbrt" => {
+                let n = if builtin.name == "sqrt" { 2 } else { 3 };
+                let dimension = first.dimension.root(n).ok_or_else(|| {
+                    KalcError::Domain(format!(
+                        "Function '{}' expects a unit with a matching power, such as m^{}",
+                        builtin.name, n
+                    ))
+                })?;
+                (dimension, None)
+            }
+            _ => {
+                let what = format!("Function '{}'", builtin.name);
+                for arg in args {
+                    without_units(&what, arg)?;
+                }
+                (Dimension::NONE, None)
+            }
+        };
+        let result = self.inner.call(builtin, &values, ieee)?;
+        Ok(Quantity::plain(result)
+            .with(dimension, unit)
+            .dated(calendar))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::RoundingMode;
+
+    use super::*;
+    use crate::numeric::{DEFAULT_PRECISION, Decimal, Exact};
+
+    /// Units over decimals, with rates that have no row for their base
+    /// currency.
+    fn units() -> Units<Decimal> {
+        let rates = Rates::parse_csv(
+            "currency,rate,date\nEUR,0.9,2026-10-16\nGBP,0.8,2026-10-17\nJPY,150,\n",
+        )
+        .unwrap();
+        let decimal = Decimal::new(DEFAULT_PRECISION, RoundingMode::HalfEven).unwrap();
+        Units::new(decimal, Some(rates))
+    }
+
+    fn quantity(units: &Units<Decimal>, n: &str, unit: &str) -> Value<Decimal> {
+        let n = units.parse(n).unwrap();
+        let unit = units.named(unit).unwrap().unwrap();
+        units.mul(&n, &unit).unwrap()
+    }
+
+    fn convert(units: &Units<Decimal>, value: &Value<Decimal>, code: &str) -> String {
+        let target = units.named(code).unwrap().unwrap();
+        let converted = units.convert(value, &target, code).unwrap();
+        units.format(&converted, Format::default())
+    }
+
+    #[test]
+    fn currencies_only_combine_once_converted() {
+        let units = units();
+        let euros = quantity(&units, "100", "EUR");
+        let pounds = quantity(&units, "2", "GBP");
+        for op in [Op::Add, Op::Sub, Op::Mul, Op::Div, Op::FloorDiv, Op::Lt] {
+            assert!(matches!(
+                units.binary(&op, &euros, &pounds),
+                Err(KalcError::DimensionMismatch { .. })
+            ));
+        }
+        let in_euros = units.convert(&pounds, &units.named("EUR").unwrap().unwrap(), "EUR");
+        let ratio = units.div(&euros, &in_euros.unwrap()).unwrap();
+        assert_eq!(
+            units.format(&ratio, Format::default()),
+            "44.44444444444444444444444444444444"
+        );
+    }
+
+    #[test]
+    fn conversions_round_once() {
+        let units = units();
+        let euros = quantity(&units, "100", "EUR");
+        assert_eq!(
+            convert(&units, &euros, "GBP"),
+            "88.88888888888888888888888888888889 GBP (rates of 2026-10-17)"
+        );
+        assert_eq!(
+            convert(&units, &quantity(&units, "90", "EUR"), "GBP"),
+            "80 GBP (rates of 2026-10-17)"
+        );
+        assert_eq!(
+            convert(&units, &euros, "JPY"),
+            "16666.66666666666666666666666666667 JPY (rates of 2026-10-17)"
+        );
+    }
+
+    #[test]
+    fn base_currency_needs_its_own_row() {
+        let units = units();
+        assert!(units.named("USD").is_none());
+        assert!(units.named("EUR").is_some());
+    }
+
+    #[test]
+    fn amounts_carry_the_date_of_the_rates() {
+        let units = units();
+        let euros = quantity(&units, "20", "EUR");
+        assert_eq!(
+            units.format(&euros, Format::default()),
+            "20 EUR (rates of 2026-10-17)"
+        );
+        let ratio = units.div(&euros, &quantity(&units, "4", "EUR")).unwrap();
+        assert_eq!(units.format(&ratio, Format::default()), "5");
+    }
+
+    #[test]
+    fn temperature_scales_have_their_own_zero() {
+        let units = units();
+        let boiling = quantity(&units, "100", "°C");
+        assert_eq!(convert(&units, &boiling, "K"), "373.15 K");
+        assert_eq!(
+            convert(&units, &quantity(&units, "300", "K"), "°C"),
+            "26.85 °C"
+        );
+        let warmer = units.add(&quantity(&units, "20", "°C"), &quantity(&units, "5", "K"));
+        assert_eq!(units.format(&warmer.unwrap(), Format::default()), "25 °C");
+
+        // Degrees Fahrenheit are 5/9 K, which only exact backends keep exact.
+        let exact = Units::new(Exact::default(), None);
+        let in_fahrenheit = |n: &str, from: &str, to: &str| {
+            let value = exact.parse(n).unwrap();
+            let value = exact.mul(&value, &exact.named(from).unwrap().unwrap());
+            let target = exact.named(to).unwrap().unwrap();
+            let converted = exact.convert(&value.unwrap(), &target, to).unwrap();
+            exact.format(&converted, Format::default())
+        };
+        assert_eq!(in_fahrenheit("100", "°C", "°F"), "212 °F");
+        assert_eq!(in_fahrenheit("-40", "degC", "degF"), "-40 degF");
+        assert_eq!(in_fahrenheit("32", "fahrenheit", "K"), "5463/20 K");
+    }
+
+    #[test]
+    fn derived_units_are_named() {
+        let units = units();
+        let charge = quantity(&units, "100", "C");
+        let capacitance = quantity(&units, "2", "F");
+        let err = units.convert(&charge, &capacitance, "F").unwrap_err();
+        assert_eq!(err.to_string(), "Incompatible units: C and F");
+        let hertz = units.div(
+            &units.parse("1").unwrap(),
+            &units.named("s").unwrap().unwrap(),
+        );
+        assert_eq!(units.format(&hertz.unwrap(), Format::default()), "1 Hz");
+        let product = units
+            .mul(&capacitance, &quantity(&units, "3", "V"))
+            .unwrap();
+        assert_eq!(units.format(&product, Format::default()), "6 C");
+    }
+
+    #[test]
+    fn currencies_need_rates() {
+        let decimal = Decimal::new(DEFAULT_PRECISION, RoundingMode::HalfEven).unwrap();
+        let units = Units::new(decimal, None);
+        assert!(matches!(units.named("EUR"), Some(Err(KalcError::Usage(_)))));
+    }
+
+    fn moment(units: &Units<Decimal>, kind: Calendar, text: &str) -> Value<Decimal> {
+        units.calendar(kind, text).unwrap()
+    }
+
+    #[test]
+    fn dates_and_durations_read_back() {
+        let units = units();
+        for (kind, text, shown) in [
+            (Calendar::Date, "2026-10-18", "2026-10-18"),
+            (Calendar::DateTime, "2026-10-18T14:30", "2026-10-18 14:30"),
+            (
+                Calendar::DateTime,
+                "2026-10-18 09:05:30",
+                "2026-10-18 09:05:30",
+            ),
+            (Calendar::Duration, "90m", "1h 30m"),
+            (Calendar::Duration, "2w", "14d"),
+            (Calendar::Duration, "1.5s", "1.5s"),
+        ] {
+            let value = moment(&units, kind, text);
+            assert_eq!(units.format(&value, Format::default()), shown, "{text}");
+        }
+        assert!(matches!(
+            units.calendar(Calendar::Date, "2026-02-30"),
+            Err(KalcError::Domain(_))
+        ));
+    }
+
+    #[test]
+    fn dates_differ_by_durations() {
+        let units = units();
+        let show = |value: Result<Value<Decimal>, KalcError>| {
+            units.format(&value.unwrap(), Format::default())
+        };
+        let day = moment(&units, Calendar::Date, "2026-10-18");
+        let christmas = moment(&units, Calendar::Date, "2026-12-25");
+        assert_eq!(show(units.sub(&christmas, &day)), "68d");
+        let hours = moment(&units, Calendar::Duration, "36h");
+        assert_eq!(show(units.add(&day, &hours)), "2026-10-19 12:00");
+        let week = moment(&units, Calendar::Duration, "1w");
+        assert_eq!(show(units.sub(&day, &week)), "2026-10-11");
+        assert!(matches!(
+            units.add(&day, &christmas),
+            Err(KalcError::Domain(_))
+        ));
+        assert!(units.sub(&week, &day).is_err());
+    }
+
+    #[test]
+    fn shifted_dates_stay_in_range() {
+        let units = units();
+        let last = moment(&units, Calendar::Date, "9999-12-31");
+        let far = moment(&units, Calendar::Duration, "100000000000000d");
+        assert!(matches!(
+            units.add(&last, &far),
+            Err(KalcError::Overflow(Some(_)))
+        ));
+        assert!(units.sub(&last, &far).is_err());
+    }
+}
@@ -0,0 +1,1203 @@
+use std::{iter::Peekable, rc::Rc, slice::Iter};
+
+use crate::{
+    calendar::Calendar,
+    constants,
+    error::{Error, KalcError, Span},
+    eval::{UserFunction, is_reserved},
+    functions,
+    lexer::{SpannedToken, Token},
+    rates, units,
+};
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    Mul,
+    Add,
+    Sub,
+    Div,
+    /// `a // b`: floor of the true quotient, `-7 // 2 = -4`.
+    FloorDiv,
+    /// `a % b`: remainder of truncated division, takes the sign of `a`.
+    Rem,
+    /// `a mod b`: Euclidean modulo, always in `[0, |b|)`.
+    Mod,
+    Pow,
+    /// Comparisons evaluate to 1 when they hold and 0 otherwise.
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    /// Bitwise operators, only defined for fixed-width integers.
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    /// `a >> b`: arithmetic shift, keeping the sign of signed integers.
+    Shr,
+    /// `a >>> b`: logical shift, shifting in zeros.
+    UShr,
+}
+
+#[derive(Debug, Clone)]
+pub enum UnaryOp {
+    Neg,
+    Plus,
+    /// `~a`, flipping every bit of a fixed-width integer.
+    BitNot,
+    /// Postfix `n!`.
+    Factorial,
+}
+
+/// An expression node and the columns of the input it was parsed from.
+#[derive(Debug, Clone)]
+pub struct ASTNode {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum NodeKind {
+    /// A number literal as written in the input.
+    Literal(String),
+    /// An imaginary literal such as `4i`, without the `i`.
+    Imaginary(String),
+    /// A date, time of day or duration literal as written in the input.
+    Calendar(Calendar, String),
+    /// A number known at parse time, such as a constant.
+    Number(f64),
+    UnaryOp {
+        op: UnaryOp,
+        operand: Box<ASTNode>,
+    },
+    Call {
+        name: String,
+        args: Vec<ASTNode>,
+    },
+    Variable(String),
+    BinaryOp {
+        left: Box<ASTNode>,
+        op: Op,
+        right: Box<ASTNode>,
+    },
+    /// `cond ? then : otherwise`, only the chosen branch is evaluated.
+    Conditional {
+        cond: Box<ASTNode>,
+        then: Box<ASTNode>,
+        otherwise: Box<ASTNode>,
+    },
+    /// `value to target`, `target` being a unit written as `label`.
+    Convert {
+        value: Box<ASTNode>,
+        target: Box<ASTNode>,
+        label: String,
+    },
+    /// `value to hex`, `value` to be written in base `radix`.
+    Radix {
+        value: Box<ASTNode>,
+        radix: u32,
+    },
+}
+
+/// Chains such as `1 + 1 + ... + 1` nest as deep as they are long, so they
+/// are taken apart from the left instead of being dropped recursively.
+impl Drop for ASTNode {
+    fn drop(&mut self) {
+        let take_left = |node: &mut ASTNode| match &mut node.kind {
+            NodeKind::BinaryOp { left, .. } => {
+                let leaf = ASTNode::new(NodeKind::Number(0.0), left.span);
+                Some(std::mem::replace(left.as_mut(), leaf))
+            }
+            _ => None,
+        };
+        let mut next = take_left(self);
+        while let Some(mut node) = next {
+            next = take_left(&mut node);
+        }
+    }
+}
+
+impl ASTNode {
+    fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    fn binary(left: ASTNode, op: Op, right: ASTNode) -> Self {
+        let span = left.span.to(right.span);
+        Self::new(
+            NodeKind::BinaryOp {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            },
+            span,
+        )
+    }
+
+    /// Whether the node is made of numbers alone, such as `1/2` or `-3`.
+    fn is_number(&self) -> bool {
+        match &self.kind {
+            NodeKind::Literal(_) | NodeKind::Number(_) => true,
+            NodeKind::UnaryOp { operand, .. } => operand.is_number(),
+            NodeKind::BinaryOp { left, right, .. } => left.is_number() && right.is_number(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Assign {
+        name: String,
+        value: ASTNode,
+    },
+    Define {
+        name: String,
+        function: Rc<UserFunction>,
+    },
+    Expr(ASTNode),
+}
+
+/// How deeply parentheses, signs and other operands may nest, keeping the
+/// recursive descent of the parser and evaluator well within the stack.
+const MAX_NESTING: usize = 64;
+
+fn syntax_error(message: impl Into<String>, span: Span) -> Error {
+    KalcError::Parse(message.into()).at(span)
+}
+
+#[derive(Debug)]
+pub struct Parser<'a> {
+    tokens: Peekable<Iter<'a, SpannedToken>>,
+    /// Span of the most recently consumed token.
+    last_span: Span,
+    /// Whether to record errors and carry on instead of stopping at the
+    /// first one.
+    recover: bool,
+    errors: Vec<Error>,
+    /// How many operands are being parsed inside one another.
+    nesting: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Peekable<Iter<'a, SpannedToken>>) -> Self {
+        Self {
+            tokens,
+            last_span: Span::default(),
+            recover: false,
+            errors: vec![],
+            nesting: 0,
+        }
+    }
+
+    /// Switches the parser to recovery mode: after an error it skips ahead to
+    /// the next operator or closing paren and goes on parsing, so that
+    /// [`Parser::parse_program`] reports every problem in one pass.
+    pub fn recovering(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+
+    fn peek(&mut self) -> Option<&'a Token> {
+        self.tokens.peek().copied().map(|t| &t.token)
+    }
+
+    /// Span of the next token, or of the last one once the input is exhausted.
+    fn peek_span(&mut self) -> Span {
+        self.tokens.peek().map_or(self.last_span, |t| t.span)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let next = self.tokens.next()?;
+        self.last_span = next.span;
+        Some(&next.token)
+    }
+
+    /// Peeks at the token after the next one.
+    fn peek_second(&self) -> Option<&'a Token> {
+        let mut ahead = self.tokens.clone();
+        ahead.next();
+        ahead.next().map(|t| &t.token)
+    }
+
+    /// A syntax error pointing at the next token.
+    fn error_at_next(&mut self, message: impl Into<String>) -> Error {
+        syntax_error(message, self.peek_span())
+    }
+
+    /// In recovery mode, records `err` and returns `Ok` so the caller can
+    /// resynchronize; otherwise hands it back to abort the parse. An error
+    /// at a span that already has one is dropped, since it is the same
+    /// mistake seen from another rule.
+    fn recover(&mut self, err: Error) -> Result<(), Error> {
+        if !self.recover {
+            return Err(err);
+        }
+        if !self.errors.iter().any(|seen| seen.span == err.span) {
+            self.errors.push(err);
+        }
+        Ok(())
+    }
+
+    /// Skips tokens up to the next one matching `stop` that is not nested in
+    /// parentheses opened along the way. Never skips past ';' or the end of
+    /// the input.
+    fn skip_until(&mut self, stop: impl Fn(&Token) -> bool) {
+        let mut depth = 0usize;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon | Token::Eof => break,
+                _ if depth == 0 && stop(token) => break,
+                Token::LParen => depth += 1,
+                Token::RParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skips a stray operand up to the next operator or closing token, where
+    /// the expression can be picked up again.
+    fn skip_to_operator(&mut self) {
+        self.skip_until(|token| {
+            token.is_operator() || matches!(token, Token::RParen | Token::Comma | Token::Colon)
+        });
+    }
+
+    /// Stands in for an operand that could not be parsed. It is never
+    /// evaluated since the parse as a whole fails.
+    fn placeholder(span: Span) -> ASTNode {
+        ASTNode::new(NodeKind::Number(f64::NAN), span)
+    }
+
+    /// Parses an operand the grammar requires, `missing` describing the
+    /// error when there is none.
+    fn expect_operand(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Option<ASTNode>, Error>,
+        missing: impl FnOnce() -> String,
+    ) -> Result<ASTNode, Error> {
+        match parse(self)? {
+            Some(operand) => Ok(operand),
+            None => {
+                let err = self.error_at_next(missing());
+                self.recover_operand(err, parse)
+            }
+        }
+    }
+
+    /// Recovers from `err` raised where an operand was expected by skipping
+    /// to the next token that can start one and parsing it with `parse`. A
+    /// closing token ends the search and leaves a placeholder instead.
+    fn recover_operand(
+        &mut self,
+        err: Error,
+        parse: fn(&mut Self) -> Result<Option<ASTNode>, Error>,
+    ) -> Result<ASTNode, Error> {
+        let span = err.span.unwrap_or(self.last_span);
+        self.recover(err)?;
+        self.skip_until(|token| {
+            token.starts_operand()
+                || matches!(
+                    token,
+                    Token::RParen | Token::Comma | Token::Question | Token::Colon
+                )
+        });
+        Ok(parse(self)?.unwrap_or_else(|| Self::placeholder(span)))
+    }
+
+    /// A syntax error for a token that cannot appear where it was found.
+    fn unexpected(&mut self) -> Error {
+        match self.peek() {
+            Some(Token::Eof) | None => self.error_at_next("Unexpected end of input"),
+            Some(Token::RParen) => self.error_at_next("Unbalanced parentheses: unexpected ')'"),
+            Some(token) => self.error_at_next(format!("Unexpected '{}'", token)),
+        }
+    }
+
+    /// Parses `;` separated statements up to the end of the input. Without
+    /// recovery mode the result holds at most one error; with it, statements
+    /// are resumed after errors and all of them are returned in input order.
+    pub fn parse_program(&mut self) -> Result<Vec<Statement>, Vec<Error>> {
+        let mut statements = vec![];
+        loop {
+            match self.peek() {
+                None | Some(Token::Eof) => break,
+                Some(Token::Semicolon) => {
+                    self.advance();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let result = self.parse_statement().and_then(|statement| {
+                statements.push(statement);
+                self.finish_statement()
+            });
+            // Whatever is left of a broken statement is skipped.
+            if let Err(err) = result {
+                self.recover(err).map_err(|err| vec![err])?;
+                self.skip_until(|_| false);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Checks that the statement just parsed ends here. In recovery mode a
+    /// stray token such as an unbalanced `)` is skipped, along with the group
+    /// it opens if it is a `(`, and the input after it is parsed on from the
+    /// next operator, so that `2 x ) + 4 x` also reports the missing value at
+    /// the end.
+    fn finish_statement(&mut self) -> Result<(), Error> {
+        loop {
+            if matches!(self.peek(), Some(Token::Eof | Token::Semicolon) | None) {
+                return Ok(());
+            }
+            let err = self.unexpected();
+            self.recover(err)?;
+            // A `(` is left for the skip, which steps over the whole group.
+            if !matches!(self.peek(), Some(Token::LParen)) {
+                self.advance();
+            }
+            self.skip_to_operator();
+            if let Some(token) = self.peek().filter(|token| token.is_operator()) {
+                self.advance();
+                self.expect_operand(Self::parse_expression, || {
+                    format!("Expected a value after '{}'", token)
+                })?;
+            }
+        }
+    }
+
+    /// Parses `let name = expr`, `name = expr`, `name(params) = expr` or a
+    /// bare expression.
+    fn parse_statement(&mut self) -> Result<Statement, Error> {
+        let has_let = matches!(self.peek(), Some(Token::Let));
+        if has_let {
+            self.advance();
+        }
+
+        if self.is_function_definition() {
+            return self.parse_function_definition();
+        }
+
+        let is_assignment = matches!(self.peek_second(), Some(Token::Assign));
+        if let (true, Some(Token::Ident(name))) = (is_assignment, self.peek()) {
+            let name = name.clone();
+            if constants::lookup(&name).is_some() {
+                return Err(self.error_at_next(format!("Cannot assign to constant '{}'", name)));
+            }
+            if is_reserved(&name) {
+                return Err(self.error_at_next(format!("Cannot assign to '{}'", name)));
+            }
+            self.advance();
+            self.advance();
+
+            let value = self.expect_operand(Self::parse_expression, || {
+                format!("Missing value in assignment to '{}'", name)
+            })?;
+            return Ok(Statement::Assign { name, value });
+        } else if has_let {
+            return Err(self.error_at_next("Expected 'name = value' after 'let'"));
+        }
+
+        let expr = self.expect_operand(Self::parse_expression, || {
+            "Expected an expression".to_string()
+        })?;
+        Ok(Statement::Expr(expr))
+    }
+
+    /// Looks ahead for `name(a, b, ...) =` without consuming anything.
+    fn is_function_definition(&self) -> bool {
+        let mut ahead = self.tokens.clone().map(|t| &t.token);
+        if !matches!(ahead.next(), Some(Token::Ident(_)))
+            || !matches!(ahead.next(), Some(Token::LParen))
+        {
+            return false;
+        }
+
+        let mut expect_param = true;
+        loop {
+            match (ahead.next(), expect_param) {
+                (Some(Token::Ident(_)), true) => expect_param = false,
+                (Some(Token::Comma), false) => expect_param = true,
+                (Some(Token::RParen), _) => break,
+                _ => return false,
+            }
+        }
+        matches!(ahead.next(), Some(Token::Assign))
+    }
+
+    fn parse_function_definition(&mut self) -> Result<Statement, Error> {
+        let Some(Token::Ident(name)) = self.advance() else {
+            return Err(self.unexpected());
+        };
+        let name = name.clone();
+        if functions::lookup(&name).is_some() {
+            return Err(syntax_error(
+                format!("Cannot redefine built-in function '{}'", name),
+                self.last_span,
+            ));
+        }
+        self.advance();
+
+        let mut params: Vec<String> = vec![];
+        while let Some(token) = self.advance() {
+            let span = self.last_span;
+            match token {
+                Token::Ident(param) if constants::lookup(param).is_some() => {
+                    return Err(syntax_error(
+                        format!("Cannot use constant '{}' as a parameter", param),
+                        span,
+                    ));
+                }
+                Token::Ident(param) if is_reserved(param) => {
+                    return Err(syntax_error(
+                        format!("Cannot use '{}' as a parameter", param),
+                        span,
+                    ));
+                }
+                Token::Ident(param) if params.contains(param) => {
+                    return Err(syntax_error(
+                        format!("Duplicate parameter '{}' in '{}'", param, name),
+                        span,
+                    ));
+                }
+                Token::Ident(param) => params.push(param.clone()),
+                Token::RParen => break,
+                _ => continue,
+            }
+        }
+        self.advance();
+
+        let body = self.expect_operand(Self::parse_expression, || {
+            format!("Missing body in definition of '{}'", name)
+        })?;
+        Ok(Statement::Define {
+            name,
+            function: Rc::new(UserFunction { params, body }),
+        })
+    }
+
+    /// A conditional, optionally converted to a unit or a base with `to` or
+    /// `in`.
+    fn parse_expression(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(expr) = self.parse_conditional()? else {
+            return Ok(None);
+        };
+        // Each conversion nests the value in one more node.
+        let nesting = self.nesting;
+        let expr = self.parse_conversions(expr);
+        self.nesting = nesting;
+        expr.map(Some)
+    }
+
+    /// `expr to unit`, `expr to hex` and so on, any number of times.
+    fn parse_conversions(&mut self, mut expr: ASTNode) -> Result<ASTNode, Error> {
+        while let Some(Token::Ident(keyword)) = self.peek() {
+            if keyword != "to" && keyword != "in" {
+                break;
+            }
+            self.nest()?;
+            self.advance();
+
+            if let Some(radix) = self.parse_radix()? {
+                let span = expr.span.to(self.last_span);
+                expr = ASTNode::new(
+                    NodeKind::Radix {
+                        value: Box::new(expr),
+                        radix,
+                    },
+                    span,
+                );
+                continue;
+            }
+            let target = self.expect_operand(Self::parse_multiplicative, || {
+                format!("Expected a unit after '{}'", keyword)
+            })?;
+            let span = expr.span.to(target.span);
+            expr = ASTNode::new(
+                NodeKind::Convert {
+                    value: Box::new(expr),
+                    label: unit_label(&target),
+                    target: Box::new(target),
+                },
+                span,
+            );
+        }
+
+        Ok(expr)
+    }
+
+    /// The base named after `to`: `hex`, `oct`, `bin`, `dec` or `base N`
+    /// for any base from 2 to 36.
+    fn parse_radix(&mut self) -> Result<Option<u32>, Error> {
+        let Some(Token::Ident(name)) = self.peek() else {
+            return Ok(None);
+        };
+        let radix = match name.as_str() {
+            "hex" => 16,
+            "oct" => 8,
+            "bin" => 2,
+            "dec" => 10,
+            "base" => {
+                let Some(Token::Number(digits)) = self.peek_second() else {
+                    return Ok(None);
+                };
+                self.advance();
+                self.advance();
+                return match digits.parse::<u32>() {
+                    Ok(radix) if (2..=36).contains(&radix) => Ok(Some(radix)),
+                    _ => Err(syntax_error(
+                        format!("Invalid base {}: expected 2 to 36", digits),
+                        self.last_span,
+                    )),
+                };
+            }
+            _ => return Ok(None),
+        };
+        self.advance();
+        Ok(Some(radix))
+    }
+
+    /// `cond ? then : otherwise`, right associative.
+    fn parse_conditional(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(cond) = self.parse_bit_or()? else {
+            return Ok(None);
+        };
+        if !matches!(self.peek(), Some(Token::Question)) {
+            return Ok(Some(cond));
+        }
+        self.advance();
+
+        let then = self.expect_operand(Self::parse_conditional, || {
+            "Missing value after '?'".to_string()
+        })?;
+        if !matches!(self.peek(), Some(Token::Colon)) {
+            return Err(self.error_at_next("Expected ':' in conditional expression"));
+        }
+        self.advance();
+        let otherwise = self.expect_operand(Self::parse_conditional, || {
+            "Missing value after ':'".to_string()
+        })?;
+
+        let span = cond.span.to(otherwise.span);
+        Ok(Some(ASTNode::new(
+            NodeKind::Conditional {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                otherwise: Box::new(otherwise),
+            },
+            span,
+        )))
+    }
+
+    /// A chain of the left associative operators `op` picks out, between
+    /// operands parsed with `operand`.
+    fn parse_chain(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Option<ASTNode>, Error>,
+        op: fn(&Token) -> Option<Op>,
+    ) -> Result<Option<ASTNode>, Error> {
+        let Some(mut expr) = operand(self)? else {
+            return Ok(None);
+        };
+
+        while let Some(token) = self.peek() {
+            let Some(op) = op(token) else {
+                break;
+            };
+            self.advance();
+
+            let right =
+                self.expect_operand(operand, || format!("Expected a value after '{}'", token))?;
+            expr = ASTNode::binary(expr, op, right);
+        }
+
+        Ok(Some(expr))
+    }
+
+    /// Bitwise operators bind more loosely than comparisons, as in C:
+    /// `a & b == c` is `a & (b == c)`.
+    fn parse_bit_or(&mut self) -> Result<Option<ASTNode>, Error> {
+        self.parse_chain(Self::parse_bit_xor, |token| {
+            matches!(token, Token::BitOr).then_some(Op::BitOr)
+        })
+    }
+
+    fn parse_bit_xor(&mut self) -> Result<Option<ASTNode>, Error> {
+        self.parse_chain(Self::parse_bit_and, |token| {
+            matches!(token, Token::BitXor).then_some(Op::BitXor)
+        })
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Option<ASTNode>, Error> {
+        self.parse_chain(Self::parse_comparison, |token| {
+            matches!(token, Token::BitAnd).then_some(Op::BitAnd)
+        })
+    }
+
+    fn parse_comparison(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(mut expr) = self.parse_shift()? else {
+            return Ok(None);
+        };
+
+        while let Some(token) = self.peek() {
+            let op = match token {
+                Token::Lt => Op::Lt,
+                Token::Le => Op::Le,
+                Token::Gt => Op::Gt,
+                Token::Ge => Op::Ge,
+                Token::Eq => Op::Eq,
+                Token::Ne => Op::Ne,
+                _ => break,
+            };
+            self.advance();
+
+            let right = self.expect_operand(Self::parse_shift, || {
+                format!("Expected a value after '{}'", token)
+            })?;
+            expr = ASTNode::binary(expr, op, right);
+        }
+
+        Ok(Some(expr))
+    }
+
+    /// Shifts bind more loosely than sums, as in C: `1 << 2 + 1` is `1 << 3`.
+    fn parse_shift(&mut self) -> Result<Option<ASTNode>, Error> {
+        self.parse_chain(Self::parse_additive, |token| match token {
+            Token::Shl => Some(Op::Shl),
+            Token::Shr => Some(Op::Shr),
+            Token::UShr => Some(Op::UShr),
+            _ => None,
+        })
+    }
+
+    fn parse_additive(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(mut expr) = self.parse_multiplicative()? else {
+            return Ok(None);
+        };
+
+        while let Some(token) = self.peek() {
+            let op = match token {
+                Token::Add => Op::Add,
+                Token::Sub => Op::Sub,
+                _ => break,
+            };
+            self.advance();
+
+            let right = self.expect_operand(Self::parse_multiplicative, || {
+                format!("Expected a value after '{}'", token)
+            })?;
+            expr = ASTNode::binary(expr, op, right);
+        }
+
+        Ok(Some(expr))
+    }
+
+    /// A unit written after a number applies to the whole term before it
+    /// when that term is made of numbers alone, so `1/2 mi` is half a mile
+    /// and `10 / 2 s` is 5 s. Otherwise it applies to the number alone:
+    /// `10 m / 2 s` is 5 m/s.
+    fn parse_multiplicative(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(mut expr) = self.parse_unary()? else {
+            return Ok(None);
+        };
+        if let Some(unit) = self.parse_unit(&expr)? {
+            expr = ASTNode::binary(expr, Op::Mul, unit);
+        }
+
+        while let Some(token) = self.peek() {
+            let op = match token {
+                Token::Mul => Op::Mul,
+                Token::Div => Op::Div,
+                Token::FloorDiv => Op::FloorDiv,
+                Token::Rem => Op::Rem,
+                Token::Mod => Op::Mod,
+                Token::Number(_)
+                | Token::Imaginary(_)
+                | Token::Date(_)
+                | Token::Time(_)
+                | Token::Duration(..) => {
+                    let err = self.error_at_next("Missing operator before number");
+                    self.recover(err)?;
+                    self.skip_to_operator();
+                    continue;
+                }
+                // Already reported by the lexer.
+                Token::Invalid => {
+                    self.skip_to_operator();
+                    continue;
+                }
+                _ => break,
+            };
+            self.advance();
+
+            let mut right = self.expect_operand(Self::parse_unary, || {
+                format!("Expected a value after '{}'", token)
+            })?;
+            match self.parse_unit(&right)? {
+                Some(unit) if expr.is_number() => {
+                    expr = ASTNode::binary(ASTNode::binary(expr, op, right), Op::Mul, unit);
+                }
+                Some(unit) => {
+                    right = ASTNode::binary(right, Op::Mul, unit);
+                    expr = ASTNode::binary(expr, op, right);
+                }
+                None => expr = ASTNode::binary(expr, op, right),
+            }
+        }
+
+        Ok(Some(expr))
+    }
+
+    /// The unit written after `number`, as in `3 m^2`, if there is one.
+    fn parse_unit(&mut self, number: &ASTNode) -> Result<Option<ASTNode>, Error> {
+        if !number.is_number() || !self.unit_follows() {
+            return Ok(None);
+        }
+        self.expect_operand(Self::parse_power, || "Expected a unit".to_string())
+            .map(Some)
+    }
+
+    /// Every operand nested in another one is parsed through here, so this
+    /// is where nesting is limited. The error ends the statement rather than
+    /// being recovered from, since the input goes on just as deep.
+    fn parse_unary(&mut self) -> Result<Option<ASTNode>, Error> {
+        let nesting = self.nesting;
+        let operand = self.nest().and_then(|()| self.parse_signed());
+        self.nesting = nesting;
+        operand
+    }
+
+    /// Goes one level deeper, unless that is past [`MAX_NESTING`].
+    fn nest(&mut self) -> Result<(), Error> {
+        if self.nesting >= MAX_NESTING {
+            return Err(self.error_at_next(format!(
+                "Expression is nested too deeply: more than {} levels",
+                MAX_NESTING
+            )));
+        }
+        self.nesting += 1;
+        Ok(())
+    }
+
+    fn parse_signed(&mut self) -> Result<Option<ASTNode>, Error> {
+        let op = match self.peek() {
+            Some(Token::Sub) => UnaryOp::Neg,
+            Some(Token::Add) => UnaryOp::Plus,
+            Some(Token::BitNot) => UnaryOp::BitNot,
+            _ => return self.parse_power(),
+        };
+        self.advance();
+        let op_span = self.last_span;
+
+        let operand = self.expect_operand(Self::parse_unary, || {
+            "Expected a value after sign".to_string()
+        })?;
+        let span = op_span.to(operand.span);
+        Ok(Some(ASTNode::new(
+            NodeKind::UnaryOp {
+                op,
+                operand: Box::new(operand),
+            },
+            span,
+        )))
+    }
+
+    /// Exponentiation binds tighter than unary minus on its left (`-2^2 = -4`)
+    /// and is right associative (`2^3^2 = 2^9`).
+    fn parse_power(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(base) = self.parse_postfix()? else {
+            return Ok(None);
+        };
+
+        if !matches!(self.peek(), Some(Token::Pow)) {
+            return Ok(Some(base));
+        }
+        self.advance();
+
+        let exponent = self.expect_operand(Self::parse_unary, || {
+            "Expected an exponent after '^'".to_string()
+        })?;
+        Ok(Some(ASTNode::binary(base, Op::Pow, exponent)))
+    }
+
+    /// Factorials bind tighter than anything else: `-3!` is `-(3!)` and
+    /// `2^3!` is `2^6`.
+    fn parse_postfix(&mut self) -> Result<Option<ASTNode>, Error> {
+        let Some(mut expr) = self.parse_primary_exp()? else {
+            return Ok(None);
+        };
+
+        while matches!(self.peek(), Some(Token::Bang)) {
+            self.advance();
+            let span = expr.span.to(self.last_span);
+            expr = ASTNode::new(
+                NodeKind::UnaryOp {
+                    op: UnaryOp::Factorial,
+                    operand: Box::new(expr),
+                },
+                span,
+            );
+        }
+
+        Ok(Some(expr))
+    }
+
+    /// Whether the next token is a unit written after a number. `in` is
+    /// taken as a conversion rather than inches when a unit follows it, as
+    /// in `3 in cm`.
+    fn unit_follows(&self) -> bool {
+        let is_unit = |token: Option<&Token>| match token {
+            Some(Token::Ident(name)) => units::lookup(name).is_some() || rates::is_code(name),
+            _ => false,
+        };
+        let mut ahead = self.tokens.clone().map(|t| &t.token);
+        let next = ahead.next();
+        let second = ahead.next();
+        match next {
+            _ if matches!(second, Some(Token::LParen)) => false,
+            Some(Token::Ident(name)) if name == "in" => {
+                !is_unit(second) || matches!(second, Some(Token::Ident(name)) if name == "in")
+            }
+            _ => is_unit(next),
+        }
+    }
+
+    fn parse_primary_exp(&mut self) -> Result<Option<ASTNode>, Error> {
+        let start = self.peek_span();
+        match self.peek() {
+            Some(Token::Number(text)) => {
+                self.advance();
+                Ok(Some(ASTNode::new(NodeKind::Literal(text.clone()), start)))
+            }
+            Some(Token::Imaginary(text)) => {
+                self.advance();
+                Ok(Some(ASTNode::new(NodeKind::Imaginary(text.clone()), start)))
+            }
+            // A date followed by a time of day is a single moment.
+            Some(Token::Date(date)) => {
+                self.advance();
+                let kind = if date.contains('T') {
+                    Calendar::DateTime
+                } else {
+                    Calendar::Date
+                };
+                let node = match self.peek() {
+                    Some(Token::Time(time)) if kind == Calendar::Date => {
+                        self.advance();
+                        let text = format!("{} {}", date, time);
+                        NodeKind::Calendar(Calendar::DateTime, text)
+                    }
+                    _ => NodeKind::Calendar(kind, date.clone()),
+                };
+                Ok(Some(ASTNode::new(node, start.to(self.last_span))))
+            }
+            Some(Token::Time(text)) => {
+                self.advance();
+                let node = NodeKind::Calendar(Calendar::Time, text.clone());
+                Ok(Some(ASTNode::new(node, start)))
+            }
+            // Durations written one after the other add up: `3h 20m`.
+            Some(Token::Duration(..)) => {
+                let mut expr: Option<ASTNode> = None;
+                while let Some(Token::Duration(amount, unit)) = self.peek() {
+                    self.advance();
+                    let text = format!("{}{}", amount, unit);
+                    let part =
+                        ASTNode::new(NodeKind::Calendar(Calendar::Duration, text), self.last_span);
+                    expr = Some(match expr {
+                        Some(expr) => ASTNode::binary(expr, Op::Add, part),
+                        None => part,
+                    });
+                }
+                Ok(expr)
+            }
+            Some(Token::LParen) => {
+                self.advance();
+                self.parse_group(start).map(Some)
+            }
+            // Already reported by the lexer, along with whatever runs into
+            // it such as the `1` of `_1`.
+            Some(Token::Invalid) => {
+                self.advance();
+                self.skip_to_operator();
+                Ok(Some(Self::placeholder(start)))
+            }
+            Some(Token::Ident(name)) => {
+                self.advance();
+                if !matches!(self.peek(), Some(Token::LParen)) {
+                    let kind = match constants::lookup(name) {
+                        Some(constant) => NodeKind::Number(constant.value),
+                        None => NodeKind::Variable(name.clone()),
+                    };
+                    return Ok(Some(ASTNode::new(kind, start)));
+                }
+                self.advance();
+                let args = self.parse_call_args(name, start)?;
+                Ok(Some(ASTNode::new(
+                    NodeKind::Call {
+                        name: name.clone(),
+                        args,
+                    },
+                    start.to(self.last_span),
+                )))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parses a comma separated argument list up to and including the closing
+    /// paren. `start` is the span of the function name.
+    fn parse_call_args(&mut self, name: &str, start: Span) -> Result<Vec<ASTNode>, Error> {
+        let mut args = vec![];
+        if matches!(self.peek(), Some(Token::RParen)) {
+            self.advance();
+            return Ok(args);
+        }
+
+        loop {
+            // A call cut short by the end of the statement is one mistake,
+            // not a missing argument as well.
+            if matches!(self.peek(), Some(Token::Semicolon | Token::Eof) | None) {
+                return Err(syntax_error(
+                    "Unbalanced parentheses: missing ')'",
+                    start.to(self.last_span),
+                ));
+            }
+            let arg = self.expect_operand(Self::parse_expression, || {
+                format!("Invalid argument in call to '{}'", name)
+            })?;
+            args.push(arg);
+
+            if !matches!(
+                self.peek(),
+                Some(Token::Comma | Token::RParen | Token::Eof) | None
+            ) {
+                let err = self.unexpected();
+                self.recover(err)?;
+                self.skip_until(|token| matches!(token, Token::Comma | Token::RParen));
+            }
+            match self.peek() {
+                Some(Token::Comma) => {
+                    self.advance();
+                }
+                Some(Token::RParen) => {
+                    self.advance();
+                    return Ok(args);
+                }
+                _ => {
+                    return Err(syntax_error(
+                        "Unbalanced parentheses: missing ')'",
+                        start.to(self.last_span),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Parses the inside of a `( ... )` group, the opening paren at `open`
+    /// already consumed.
+    fn parse_group(&mut self, open: Span) -> Result<ASTNode, Error> {
+        let expr = match self.parse_expression()? {
+            Some(expr) => expr,
+            None => match self.peek() {
+                Some(Token::RParen) => {
+                    let span = open.to(self.peek_span());
+                    self.recover(syntax_error("Empty parentheses", span))?;
+                    Self::placeholder(span)
+                }
+                Some(Token::Eof | Token::Semicolon) | None => {
+                    return Err(syntax_error("Unbalanced parentheses: missing ')'", open));
+                }
+                _ => {
+                    let err = self.unexpected();
+                    self.recover_operand(err, Self::parse_expression)?
+                }
+            },
+        };
+
+        if !matches!(self.peek(), Some(Token::RParen | Token::Eof) | None) {
+            let err = self.unexpected();
+            self.recover(err)?;
+            self.skip_until(|token| matches!(token, Token::RParen));
+        }
+        match self.peek() {
+            Some(Token::RParen) => {
+                self.advance();
+                Ok(expr)
+            }
+            _ => Err(syntax_error("Unbalanced parentheses: missing ')'", open)),
+        }
+    }
+}
+
+/// Writes the target of a conversion back out, as it will be printed after
+/// the converted value: `m/s^2`, `kW·h`.
+fn unit_label(node: &ASTNode) -> String {
+    match &node.kind {
+        NodeKind::Variable(name) | NodeKind::Literal(name) => name.clone(),
+        NodeKind::BinaryOp { left, op, right } => {
+            let operator = match op {
+                Op::Mul => "·",
+                Op::Div => "/",
+                Op::Pow => "^",
+                _ => " ",
+            };
+            format!("{}{}{}", unit_label(left), operator, unit_label(right))
+        }
+        NodeKind::UnaryOp {
+            op: UnaryOp::Neg,
+            operand,
+        } => format!("-{}", unit_label(operand)),
+        _ => "unit".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    /// The messages and start columns of every error in `input`.
+    fn errors(input: &str) -> Vec<(String, usize)> {
+        let chars = input.chars().collect::<Vec<_>>();
+        let (tokens, lex_errors) = tokenize(chars.iter().peekable());
+        assert!(lex_errors.is_empty(), "{:?}", lex_errors);
+        let mut parser = Parser::new(tokens.iter().peekable()).recovering();
+        let errors = parser.parse_program().expect_err("input is invalid");
+        errors
+            .iter()
+            .map(|err| (err.kind.to_string(), err.span.expect("has a span").start))
+            .collect()
+    }
+
+    /// `input` with its binary operators in parentheses, `((1 / 2) x mi)`.
+    fn grouped(input: &str) -> String {
+        fn show(node: &ASTNode) -> String {
+            match &node.kind {
+                NodeKind::Literal(text) | NodeKind::Variable(text) => text.clone(),
+                NodeKind::UnaryOp { operand, .. } => format!("-{}", show(operand)),
+                NodeKind::BinaryOp { left, op, right } => {
+                    let op = match op {
+                        Op::Mul => "x",
+                        Op::Div => "/",
+                        Op::Add => "+",
+                        Op::Pow => "^",
+                        _ => "?",
+                    };
+                    format!("({} {} {})", show(left), op, show(right))
+                }
+                kind => format!("{:?}", kind),
+            }
+        }
+        let chars = input.chars().collect::<Vec<_>>();
+        let (tokens, _) = tokenize(chars.iter().peekable());
+        let statements = Parser::new(tokens.iter().peekable())
+            .parse_program()
+            .expect("input is valid");
+        match &statements[..] {
+            [Statement::Expr(node)] => show(node),
+            _ => panic!("expected one expression"),
+        }
+    }
+
+    #[test]
+    fn units_apply_to_terms_of_numbers() {
+        assert_eq!(grouped("1/2 mi"), "((1 / 2) x mi)");
+        assert_eq!(grouped("10 / 2 s"), "((10 / 2) x s)");
+        assert_eq!(grouped("6 ft + 1/2 in"), "((6 x ft) + ((1 / 2) x in))");
+        assert_eq!(grouped("-3 m^2"), "(-3 x (m ^ 2))");
+    }
+
+    #[test]
+    fn units_apply_to_one_number_after_a_quantity() {
+        assert_eq!(grouped("10 m / 2 s"), "((10 x m) / (2 x s))");
+        assert_eq!(grouped("x / 2 s"), "(x / (2 x s))");
+        assert_eq!(
+            grouped("5 kg x 9.81 m/s^2"),
+            "(((5 x kg) x (9.81 x m)) / (s ^ 2))"
+        );
+    }
+
+    #[test]
+    fn resumes_after_a_stray_paren() {
+        assert_eq!(
+            errors("2 x ) + 4 x"),
+            [
+                ("Expected a value after 'x'".to_string(), 4),
+                ("Expected a value after 'x'".to_string(), 11),
+            ]
+        );
+        assert_eq!(
+            errors("(1 + 2)) + 3 +"),
+            [
+                ("Unbalanced parentheses: unexpected ')'".to_string(), 7),
+                ("Expected a value after '+'".to_string(), 14),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_each_mistake_once() {
+        assert_eq!(errors("2 x )").len(), 1);
+        assert_eq!(
+            errors("sqrt(;"),
+            [("Unbalanced parentheses: missing ')'".to_string(), 0)]
+        );
+        assert_eq!(errors("f(1,").len(), 1);
+    }
+
+    #[test]
+    fn skips_what_follows_a_mistake() {
+        assert_eq!(
+            errors("3 (4) + 2 x"),
+            [
+                ("Unexpected '('".to_string(), 2),
+                ("Expected a value after 'x'".to_string(), 11),
+            ]
+        );
+        assert_eq!(errors("3 (4 (5)) (6)").len(), 1);
+
+        // The lexer has reported these already.
+        for input in ["_1", "1 +\t2", "2 x _3 + 4"] {
+            let chars = input.chars().collect::<Vec<_>>();
+            let (tokens, lex_errors) = tokenize(chars.iter().peekable());
+            assert_eq!(lex_errors.len(), 1, "{input}");
+            let mut parser = Parser::new(tokens.iter().peekable()).recovering();
+            assert!(parser.parse_program().is_ok(), "{input}");
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let deep = |open: &str, depth: usize| open.repeat(depth) + "1";
+        let too_deep = [
+            ("(", MAX_NESTING + 1),
+            ("-", 100_000),
+            ("2^", MAX_NESTING + 1),
+            ("sqrt(", MAX_NESTING + 1),
+        ];
+        for (open, depth) in too_deep {
+            let found = errors(&deep(open, depth));
+            assert_eq!(found.len(), 1, "{open}");
+            assert!(found[0].0.starts_with("Expression is nested too deeply"));
+        }
+        let chain = vec!["1"; 100_000].join(" + ");
+        let chars = chain.chars().collect::<Vec<_>>();
+        let (tokens, _) = tokenize(chars.iter().peekable());
+        assert!(
+            Parser::new(tokens.iter().peekable())
+                .parse_program()
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::{fs, io, path::Path};
+
+use serde_json::Value;
+
+use crate::error::KalcError;
+
+/// Environment variable naming the rates file used when `--rates` is not
+/// given.
+pub const RATES_VAR: &str = "KALC_RATES";
+
+/// Exchange rates read from a local file. Every rate is the amount of a
+/// currency worth one unit of the same base currency, so `EUR,0.92` with a
+/// USD base means one dollar buys 0.92 euros.
+#[derive(Debug, Clone)]
+pub struct Rates {
+    /// The day the rates were published, as written in the file.
+    pub date: String,
+    /// Currency codes and their rates, kept as written so that exact
+    /// backends can read them without rounding.
+    rates: Vec<(String, String)>,
+}
+
+impl Rates {
+    /// Reads a rates file, JSON when its name ends in `.json` and CSV
+    /// otherwise.
+    ///
+    /// JSON files hold `{"date": "2026-10-17", "base": "USD", "rates":
+    /// {"EUR": 0.92, ...}}`. CSV files hold `currency,rate,date` rows, an
+    /// optional header row first.
+    pub fn load(path: &Path) -> Result<Rates, KalcError> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            KalcError::Io(io::Error::new(
+                err.kind(),
+                format!("Cannot read rates file {}: {}", path.display(), err),
+            ))
+        })?;
+        let is_json = path.extension().is_some_and(|ext| ext == "json");
+        let rates = if is_json {
+            Self::parse_json(&text)
+        } else {
+            Self::parse_csv(&text)
+        };
+        rates.map_err(|problem| {
+            KalcError::Usage(format!(
+                "Invalid rates file {}: {}",
+                path.display(),
+                problem
+            ))
+        })
+    }
+
+    fn parse_json(text: &str) -> Result<Rates, String> {
+        let json: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        let date = json["date"]
+            .as_str()
+            .ok_or("expected a \"date\" string")?
+            .to_string();
+        let table = json["rates"]
+            .as_object()
+            .ok_or("expected a \"rates\" object")?;
+
+        let mut rates = vec![];
+        if let Some(base) = json["base"].as_str() {
+            rates.push((base.to_string(), "1".to_string()));
+        }
+        for (code, rate) in table {
+            let rate = match rate {
+                Value::Number(n) => n.to_string(),
+                Value::String(s) => s.clone(),
+                _ => return Err(format!("expected a number as the rate of {}", code)),
+            };
+            rates.push((code.clone(), rate));
+        }
+        Self::new(date, rates)
+    }
+
+    /// Reads rates in CSV, `currency,rate,date` rows with an optional header.
+    pub fn parse_csv(text: &str) -> Result<Rates, String> {
+        let mut date: Option<&str> = None;
+        let mut rates = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            match fields.as_slice() {
+                [""] => continue,
+                // The header, if there is one.
+                [_, rate, ..] if number == 0 && rate.parse::<f64>().is_err() => continue,
+                [code, rate] | [code, rate, ""] => rates.push((code.to_string(), rate.to_string())),
+                [code, rate, day] => {
+                    rates.push((code.to_string(), rate.to_string()));
+                    // Dates are ISO 8601, so the latest sorts last.
+                    date = date.max(Some(day));
+                }
+                _ => return Err(format!("line {}: expected currency,rate,date", number + 1)),
+            }
+        }
+        let date = date.ok_or("no date column")?;
+        Self::new(date.to_string(), rates)
+    }
+
+    fn new(date: String, rates: Vec<(String, String)>) -> Result<Rates, String> {
+        for (code, rate) in &rates {
+            if !is_code(code) {
+                return Err(format!("'{}' is not a three-letter currency code", code));
+            }
+            if !rate.parse::<f64>().is_ok_and(|rate| rate > 0.0) {
+                return Err(format!("the rate of {} is not a positive number", code));
+            }
+        }
+        Ok(Rates { date, rates })
+    }
+
+    /// The rate of the currency `code`, as written in the file.
+    pub fn get(&self, code: &str) -> Option<&str> {
+        self.rates
+            .iter()
+            .find(|(name, _)| name == code)
+            .map(|(_, rate)| rate.as_str())
+    }
+}
+
+/// Whether `name` looks like an ISO 4217 currency code such as `USD`.
+pub fn is_code(name: &str) -> bool {
+    name.len() == 3 && name.bytes().all(|b| b.is_ascii_uppercase())
+}
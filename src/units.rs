@@ -0,0 +1,501 @@
+use std::fmt;
+
+/// Symbols of the SI base units, in the order their exponents are kept in a
+/// [`Dimension`], followed by money in whichever currency the exchange rates
+/// are based on.
+const BASE_UNITS: [&str; 8] = ["m", "kg", "s", "A", "K", "mol", "cd", "¤"];
+
+/// Exponents of the SI base units a quantity is measured in. Velocity is
+/// `m^1 s^-1`, a plain number has all exponents zero.
+///
+/// Amounts of money also carry their currency, so that dollars and euros
+/// cannot be added without converting one of them first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Dimension {
+    exponents: [i8; 8],
+    currency: Option<[u8; 3]>,
+}
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension::new([0; 8]);
+
+    const fn new(exponents: [i8; 8]) -> Dimension {
+        Dimension {
+            exponents,
+            currency: None,
+        }
+    }
+
+    /// Money in the currency `code`, which must be three ASCII letters.
+    pub fn money(code: &str) -> Dimension {
+        let mut exponents = [0; 8];
+        exponents[7] = 1;
+        Dimension {
+            exponents,
+            currency: code.as_bytes().try_into().ok(),
+        }
+    }
+
+    pub fn is_none(self) -> bool {
+        self == Self::NONE
+    }
+
+    /// The currency of an amount of money.
+    pub fn currency(&self) -> Option<&str> {
+        self.currency
+            .as_ref()
+            .and_then(|code| std::str::from_utf8(code).ok())
+    }
+
+    /// The power of money in the dimension, 1 for an amount and -1 for a
+    /// quantity per amount.
+    pub fn money_exponent(&self) -> i8 {
+        self.exponents[7]
+    }
+
+    /// Whether `self` and `other` measure the same thing, possibly in
+    /// different currencies.
+    pub fn converts_to(self, other: Dimension) -> bool {
+        self.exponents == other.exponents
+    }
+
+    pub fn mul(self, other: Dimension) -> Dimension {
+        self.combine(other, |a, b| a + b)
+    }
+
+    pub fn div(self, other: Dimension) -> Dimension {
+        self.combine(other, |a, b| a - b)
+    }
+
+    fn combine(self, other: Dimension, op: fn(i8, i8) -> i8) -> Dimension {
+        Dimension {
+            exponents: std::array::from_fn(|i| op(self.exponents[i], other.exponents[i])),
+            currency: self.currency.or(other.currency),
+        }
+        .priced()
+    }
+
+    /// Drops the currency once no money is left in the dimension, as in
+    /// the ratio of two amounts.
+    fn priced(self) -> Dimension {
+        Dimension {
+            currency: self.currency.filter(|_| self.exponents[7] != 0),
+            ..self
+        }
+    }
+
+    /// The dimension raised to `n`, unless an exponent gets out of range.
+    pub fn pow(self, n: i32) -> Option<Dimension> {
+        let mut exponents = [0; 8];
+        for (exponent, base) in exponents.iter_mut().zip(self.exponents) {
+            *exponent = i8::try_from(i32::from(base).checked_mul(n)?).ok()?;
+        }
+        Some(Dimension { exponents, ..self }.priced())
+    }
+
+    /// The `n`th root, when every exponent is divisible by `n`.
+    pub fn root(self, n: i8) -> Option<Dimension> {
+        self.exponents
+            .iter()
+            .all(|exponent| exponent % n == 0)
+            .then(|| Dimension {
+                exponents: self.exponents.map(|exponent| exponent / n),
+                ..self
+            })
+    }
+
+    /// The name of a derived unit for this dimension, such as `N`, or the
+    /// base units it is made of, such as `m/s^2`.
+    pub fn unit_name(self) -> String {
+        DERIVED
+            .iter()
+            .find(|(_, dimension)| *dimension == self)
+            .map_or_else(|| self.to_string(), |(name, _)| name.to_string())
+    }
+}
+
+/// Writes the dimension in SI base units, `kg·m/s^2`.
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_none() {
+            return f.write_str("no unit");
+        }
+
+        let power = |name: &str, exponent: i8| match exponent {
+            1 => name.to_string(),
+            _ => format!("{}^{}", name, exponent),
+        };
+        let names = self.base_names();
+        let numerator = names
+            .iter()
+            .zip(self.exponents)
+            .filter(|(_, exponent)| *exponent > 0)
+            .map(|(name, exponent)| power(name, exponent))
+            .collect::<Vec<_>>();
+        let denominator = names
+            .iter()
+            .zip(self.exponents)
+            .filter(|(_, exponent)| *exponent < 0)
+            .map(|(name, exponent)| power(name, -exponent))
+            .collect::<Vec<_>>();
+
+        match (numerator.is_empty(), denominator.len()) {
+            (_, 0) => write!(f, "{}", numerator.join("·")),
+            (true, _) => write!(f, "1/{}", denominator.join("·")),
+            (false, 1) => write!(f, "{}/{}", numerator.join("·"), denominator[0]),
+            (false, _) => write!(f, "{}/({})", numerator.join("·"), denominator.join("·")),
+        }
+    }
+}
+
+impl Dimension {
+    /// Symbols of the base units, money written in its currency.
+    fn base_names(&self) -> [&str; 8] {
+        let mut names = BASE_UNITS;
+        if let Some(currency) = self.currency() {
+            names[7] = currency;
+        }
+        names
+    }
+}
+
+const fn dimension(m: i8, kg: i8, s: i8, a: i8) -> Dimension {
+    Dimension::new([m, kg, s, a, 0, 0, 0, 0])
+}
+
+const LENGTH: Dimension = dimension(1, 0, 0, 0);
+const AREA: Dimension = dimension(2, 0, 0, 0);
+const VOLUME: Dimension = dimension(3, 0, 0, 0);
+const MASS: Dimension = dimension(0, 1, 0, 0);
+pub const TIME: Dimension = dimension(0, 0, 1, 0);
+pub const TEMPERATURE: Dimension = Dimension::new([0, 0, 0, 0, 1, 0, 0, 0]);
+const FREQUENCY: Dimension = dimension(0, 0, -1, 0);
+const CAPACITANCE: Dimension = dimension(-2, -1, 4, 2);
+const INDUCTANCE: Dimension = dimension(2, 1, -2, -2);
+const SPEED: Dimension = dimension(1, 0, -1, 0);
+const FORCE: Dimension = dimension(1, 1, -2, 0);
+const ENERGY: Dimension = dimension(2, 1, -2, 0);
+const POWER: Dimension = dimension(2, 1, -3, 0);
+const PRESSURE: Dimension = dimension(-1, 1, -2, 0);
+
+/// Names used to print quantities that have no unit of their own, such as
+/// the result of `5 kg x 9.81 m/s^2`.
+const DERIVED: &[(&str, Dimension)] = &[
+    ("m", LENGTH),
+    ("kg", MASS),
+    ("s", TIME),
+    ("N", FORCE),
+    ("J", ENERGY),
+    ("W", POWER),
+    ("Pa", PRESSURE),
+    ("C", dimension(0, 0, 1, 1)),
+    ("V", dimension(2, 1, -3, -1)),
+    ("Ω", dimension(2, 1, -3, -2)),
+    ("Hz", FREQUENCY),
+    ("F", CAPACITANCE),
+    ("H", INDUCTANCE),
+];
+
+/// A unit of measurement.
+pub struct Unit {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub dimension: Dimension,
+    /// Size of the unit in SI base units, written as a decimal or a fraction
+    /// so that exact backends keep it exact.
+    pub factor: &'static str,
+    /// Whether SI prefixes such as `k` and `m` apply to the unit.
+    pub prefixable: bool,
+    pub help: &'static str,
+    /// For a temperature scale that does not start at absolute zero, such
+    /// as °C, where it starts in kelvin.
+    pub offset: Option<&'static str>,
+}
+
+/// An SI prefix and the power of ten it stands for.
+pub struct Prefix {
+    pub name: &'static str,
+    pub factor: &'static str,
+}
+
+macro_rules! unit {
+    ($name:literal, $aliases:expr, $dimension:expr, $factor:literal, $prefixable:literal, $help:literal) => {
+        Unit {
+            name: $name,
+            aliases: $aliases,
+            dimension: $dimension,
+            factor: $factor,
+            prefixable: $prefixable,
+            help: $help,
+            offset: None,
+        }
+    };
+    ($name:literal, $aliases:expr, $dimension:expr, $factor:literal, $help:literal, offset: $offset:literal) => {
+        Unit {
+            name: $name,
+            aliases: $aliases,
+            dimension: $dimension,
+            factor: $factor,
+            prefixable: false,
+            help: $help,
+            offset: Some($offset),
+        }
+    };
+}
+
+pub const UNITS: &[Unit] = &[
+    unit!("m", &["meter", "metre"], LENGTH, "1", true, "meter"),
+    unit!("in", &["inch"], LENGTH, "0.0254", false, "inch"),
+    unit!("ft", &["foot", "feet"], LENGTH, "0.3048", false, "foot"),
+    unit!("yd", &["yard"], LENGTH, "0.9144", false, "yard"),
+    unit!("mi", &["mile"], LENGTH, "1609.344", false, "mile"),
+    unit!("nmi", &[], LENGTH, "1852", false, "nautical mile"),
+    unit!("ha", &["hectare"], AREA, "10000", false, "hectare"),
+    unit!("acre", &[], AREA, "4046.8564224", false, "acre"),
+    unit!(
+        "L",
+        &["l", "liter", "litre"],
+        VOLUME,
+        "0.001",
+        true,
+        "liter"
+    ),
+    unit!(
+        "gal",
+        &["gallon"],
+        VOLUME,
+        "0.003785411784",
+        false,
+        "US gallon"
+    ),
+    unit!("g", &["gram"], MASS, "0.001", true, "gram"),
+    unit!("t", &["tonne"], MASS, "1000", false, "metric ton"),
+    unit!("lb", &["pound"], MASS, "0.45359237", false, "pound"),
+    unit!("oz", &["ounce"], MASS, "0.028349523125", false, "ounce"),
+    unit!(
+        "s",
+        &["sec", "second", "seconds"],
+        TIME,
+        "1",
+        true,
+        "second"
+    ),
+    unit!("min", &["minute", "minutes"], TIME, "60", false, "minute"),
+    unit!("h", &["hr", "hour", "hours"], TIME, "3600", false, "hour"),
+    unit!("day", &["d", "days"], TIME, "86400", false, "day"),
+    unit!("week", &["wk", "weeks"], TIME, "604800", false, "week"),
+    unit!(
+        "year",
+        &["yr", "years"],
+        TIME,
+        "31557600",
+        false,
+        "Julian year, 365.25 days"
+    ),
+    unit!("mph", &[], SPEED, "0.44704", false, "miles per hour"),
+    unit!("kph", &[], SPEED, "5/18", false, "kilometers per hour"),
+    unit!(
+        "kn",
+        &["knot"],
+        SPEED,
+        "463/900",
+        false,
+        "knot, a nautical mile per hour"
+    ),
+    unit!("A", &["amp"], dimension(0, 0, 0, 1), "1", true, "ampere"),
+    unit!("K", &["kelvin"], TEMPERATURE, "1", true, "kelvin"),
+    unit!(
+        "°C",
+        &["degC", "celsius"],
+        TEMPERATURE,
+        "1",
+        "degree Celsius",
+        offset: "273.15"
+    ),
+    unit!(
+        "°F",
+        &["degF", "fahrenheit"],
+        TEMPERATURE,
+        "5/9",
+        "degree Fahrenheit",
+        offset: "45967/180"
+    ),
+    unit!(
+        "mol",
+        &[],
+        Dimension::new([0, 0, 0, 0, 0, 1, 0, 0]),
+        "1",
+        true,
+        "mole"
+    ),
+    unit!(
+        "cd",
+        &[],
+        Dimension::new([0, 0, 0, 0, 0, 0, 1, 0]),
+        "1",
+        true,
+        "candela"
+    ),
+    unit!("Hz", &[], FREQUENCY, "1", true, "hertz"),
+    unit!("N", &["newton"], FORCE, "1", true, "newton"),
+    unit!("lbf", &[], FORCE, "4.4482216152605", false, "pound-force"),
+    unit!("J", &["joule"], ENERGY, "1", true, "joule"),
+    unit!("Wh", &[], ENERGY, "3600", true, "watt-hour"),
+    unit!("cal", &[], ENERGY, "4.184", true, "calorie"),
+    unit!(
+        "eV",
+        &[],
+        ENERGY,
+        "0.0000000000000000001602176634",
+        true,
+        "electronvolt"
+    ),
+    unit!("W", &["watt"], POWER, "1", true, "watt"),
+    unit!(
+        "hp",
+        &[],
+        POWER,
+        "745.69987158227022",
+        false,
+        "mechanical horsepower"
+    ),
+    unit!("Pa", &["pascal"], PRESSURE, "1", true, "pascal"),
+    unit!("bar", &[], PRESSURE, "100000", true, "bar"),
+    unit!("atm", &[], PRESSURE, "101325", false, "standard atmosphere"),
+    unit!(
+        "psi",
+        &[],
+        PRESSURE,
+        "6894.757293168361",
+        false,
+        "pound per square inch"
+    ),
+    unit!(
+        "C",
+        &["coulomb"],
+        dimension(0, 0, 1, 1),
+        "1",
+        true,
+        "coulomb"
+    ),
+    unit!("V", &["volt"], dimension(2, 1, -3, -1), "1", true, "volt"),
+    unit!("Ω", &["ohm"], dimension(2, 1, -3, -2), "1", true, "ohm"),
+    unit!("F", &["farad"], CAPACITANCE, "1", true, "farad"),
+    unit!("H", &["henry"], INDUCTANCE, "1", true, "henry"),
+];
+
+pub const PREFIXES: &[Prefix] = &[
+    Prefix {
+        name: "da",
+        factor: "10",
+    },
+    Prefix {
+        name: "Q",
+        factor: "1000000000000000000000000000000",
+    },
+    Prefix {
+        name: "R",
+        factor: "1000000000000000000000000000",
+    },
+    Prefix {
+        name: "Y",
+        factor: "1000000000000000000000000",
+    },
+    Prefix {
+        name: "Z",
+        factor: "1000000000000000000000",
+    },
+    Prefix {
+        name: "E",
+        factor: "1000000000000000000",
+    },
+    Prefix {
+        name: "P",
+        factor: "1000000000000000",
+    },
+    Prefix {
+        name: "T",
+        factor: "1000000000000",
+    },
+    Prefix {
+        name: "G",
+        factor: "1000000000",
+    },
+    Prefix {
+        name: "M",
+        factor: "1000000",
+    },
+    Prefix {
+        name: "k",
+        factor: "1000",
+    },
+    Prefix {
+        name: "h",
+        factor: "100",
+    },
+    Prefix {
+        name: "d",
+        factor: "0.1",
+    },
+    Prefix {
+        name: "c",
+        factor: "0.01",
+    },
+    Prefix {
+        name: "m",
+        factor: "0.001",
+    },
+    Prefix {
+        name: "µ",
+        factor: "0.000001",
+    },
+    Prefix {
+        name: "u",
+        factor: "0.000001",
+    },
+    Prefix {
+        name: "n",
+        factor: "0.000000001",
+    },
+    Prefix {
+        name: "p",
+        factor: "0.000000000001",
+    },
+    Prefix {
+        name: "f",
+        factor: "0.000000000000001",
+    },
+    Prefix {
+        name: "a",
+        factor: "0.000000000000000001",
+    },
+    Prefix {
+        name: "z",
+        factor: "0.000000000000000000001",
+    },
+    Prefix {
+        name: "y",
+        factor: "0.000000000000000000000001",
+    },
+];
+
+/// Finds a unit by name, alias or prefixed symbol (`km`, `µs`). Names are
+/// matched as written before prefixes are tried, so `ft` is a foot rather
+/// than a femto-tonne.
+pub fn lookup(name: &str) -> Option<(Option<&'static Prefix>, &'static Unit)> {
+    let by_name = |name: &str| {
+        UNITS
+            .iter()
+            .find(|unit| unit.name == name || unit.aliases.contains(&name))
+    };
+    if let Some(unit) = by_name(name) {
+        return Some((None, unit));
+    }
+
+    PREFIXES.iter().find_map(|prefix| {
+        let unit = UNITS
+            .iter()
+            .find(|unit| unit.prefixable && Some(unit.name) == name.strip_prefix(prefix.name))?;
+        Some((Some(prefix), unit))
+    })
+}